@@ -0,0 +1,85 @@
+//! Compares catr's throughput with the system `cat` on a generated file.
+//!
+//! Run with `cargo bench`; set `CATR_BENCH_MB` to change the input size.
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+const CATR: &str = env!("CARGO_BIN_EXE_catr");
+const RUNS: usize = 3;
+
+fn generate(path: &Path, megabytes: usize) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    let mut written = 0;
+    let mut n: usize = 0;
+    while written < megabytes * 1024 * 1024 {
+        let line = format!(
+            "{:08} level=info msg=\"request handled\" took={}ms{}\n",
+            n,
+            n % 997,
+            "x".repeat(n % 80)
+        );
+        out.write_all(line.as_bytes())?;
+        written += line.len();
+        n += 1;
+    }
+    out.flush()
+}
+
+/// Runs `program` with `args` and drains its stdout through a pipe,
+/// returning the fastest of `RUNS` runs.
+fn time(program: &str, args: &[&str]) -> io::Result<Duration> {
+    let mut best = Duration::MAX;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        let mut child = Command::new(program)
+            .args(args)
+            .stdout(Stdio::piped())
+            .spawn()?;
+        io::copy(child.stdout.as_mut().unwrap(), &mut io::sink())?;
+        child.wait()?;
+        best = best.min(start.elapsed());
+    }
+    Ok(best)
+}
+
+fn main() -> io::Result<()> {
+    let megabytes = env::var("CATR_BENCH_MB")
+        .ok()
+        .and_then(|mb| mb.parse().ok())
+        .unwrap_or(64);
+    let path = env::temp_dir().join(format!("catr-bench-{}.log", std::process::id()));
+    generate(&path, megabytes)?;
+    let input = path.to_str().unwrap();
+
+    println!("{:<12} {:>10} {:>12}", "command", "time", "throughput");
+    for (program, args) in [
+        (CATR, vec!["--paging=never", input]),
+        ("cat", vec![input]),
+        (CATR, vec!["--paging=never", "-n", input]),
+        ("cat", vec!["-n", input]),
+        (CATR, vec!["--paging=never", "-A", input]),
+        ("cat", vec!["-A", input]),
+    ] {
+        let name = Path::new(program).file_name().unwrap().to_string_lossy();
+        let label = format!("{} {}", name, args[..args.len() - 1].join(" "))
+            .replace("--paging=never", "")
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        match time(program, &args) {
+            Ok(elapsed) => println!(
+                "{:<12} {:>8.0}ms {:>8.1}MB/s",
+                label,
+                elapsed.as_secs_f64() * 1000.0,
+                megabytes as f64 / elapsed.as_secs_f64()
+            ),
+            Err(err) => println!("{:<12} {}", label, err),
+        }
+    }
+    fs::remove_file(&path)
+}
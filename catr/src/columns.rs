@@ -0,0 +1,147 @@
+use unicode_width::UnicodeWidthChar;
+
+const TAB_STOP: usize = 8;
+
+/// One piece of a line as a terminal lays it out.
+enum Cell {
+    /// A character, or a byte that is not valid UTF-8, and its width.
+    Char(usize),
+    Tab,
+    /// An ANSI escape sequence, which takes no space.
+    Escape,
+}
+
+impl Cell {
+    /// The column this cell ends at when it starts at `col`.
+    fn end(&self, col: usize) -> usize {
+        match self {
+            Cell::Char(width) => col + width,
+            Cell::Tab => col + TAB_STOP - col % TAB_STOP,
+            Cell::Escape => col,
+        }
+    }
+}
+
+/// Splits the start of `line` into its first cell, returning the cell and
+/// its length in bytes.
+fn next_cell(line: &[u8]) -> (Cell, usize) {
+    match line {
+        [b'\t', ..] => (Cell::Tab, 1),
+        [0x1b, b'[', rest @ ..] => {
+            let len = rest
+                .iter()
+                .position(|b| (0x40..=0x7e).contains(b))
+                .map_or(line.len(), |end| end + 3);
+            (Cell::Escape, len)
+        }
+        [lead, ..] => {
+            let len = match lead {
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                0xf0..=0xf7 => 4,
+                _ => 1,
+            };
+            match line.get(..len).map(std::str::from_utf8) {
+                Some(Ok(s)) => {
+                    let c = s.chars().next().unwrap_or_default();
+                    (Cell::Char(c.width().unwrap_or(0)), len)
+                }
+                _ => (Cell::Char(1), 1),
+            }
+        }
+        [] => (Cell::Char(0), 0),
+    }
+}
+
+/// Iterates over the cells of `line` with their bytes, giving the column
+/// each one ends at when the line starts at column `col`.
+fn cells(line: &[u8], mut col: usize) -> impl Iterator<Item = (&[u8], Cell, usize)> {
+    let mut rest = line;
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        let (cell, len) = next_cell(rest);
+        let (bytes, tail) = rest.split_at(len);
+        rest = tail;
+        col = cell.end(col);
+        Some((bytes, cell, col))
+    })
+}
+
+/// How many columns `line` takes, with tabs at every 8th column.
+pub fn width(line: &[u8]) -> usize {
+    cells(line, 0).last().map_or(0, |(_, _, col)| col)
+}
+
+/// Replaces tabs with spaces up to the next multiple of `stop` columns, as
+/// `expand -t` does.
+pub fn expand_tabs(line: &[u8], stop: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(line.len());
+    let mut col = 0;
+    let mut rest = line;
+    while !rest.is_empty() {
+        let (cell, len) = next_cell(rest);
+        match cell {
+            Cell::Tab => {
+                let spaces = stop - col % stop;
+                out.resize(out.len() + spaces, b' ');
+                col += spaces;
+            }
+            Cell::Char(width) => {
+                out.extend_from_slice(&rest[..len]);
+                col += width;
+            }
+            Cell::Escape => out.extend_from_slice(&rest[..len]),
+        }
+        rest = &rest[len..];
+    }
+    out
+}
+
+/// Breaks `line` so that no row is wider than `columns`, starting each
+/// continuation row with `marker`. Every row holds at least one character.
+pub fn wrap(line: &[u8], columns: usize, marker: &[u8]) -> Vec<u8> {
+    let indent = width(marker);
+    let mut out = Vec::with_capacity(line.len());
+    let mut start = 0;
+    let mut col = 0;
+    let mut rest = line;
+    while !rest.is_empty() {
+        let (cell, len) = next_cell(rest);
+        let end = cell.end(col);
+        if end > columns && col > start {
+            out.push(b'\n');
+            out.extend_from_slice(marker);
+            start = indent;
+            col = indent;
+            continue;
+        }
+        out.extend_from_slice(&rest[..len]);
+        col = end;
+        rest = &rest[len..];
+    }
+    out
+}
+
+/// Cuts `line` to `columns`, ending it with an ellipsis if anything was
+/// cut. Colors left open by the cut are reset.
+pub fn truncate(line: &[u8], columns: usize) -> Vec<u8> {
+    if width(line) <= columns {
+        return line.to_vec();
+    }
+    let mut out = Vec::with_capacity(line.len());
+    let mut escaped = false;
+    for (bytes, cell, col) in cells(line, 0) {
+        if col >= columns {
+            break;
+        }
+        escaped |= matches!(cell, Cell::Escape);
+        out.extend_from_slice(bytes);
+    }
+    out.extend_from_slice("…".as_bytes());
+    if escaped {
+        out.extend_from_slice(b"\x1b[0m");
+    }
+    out
+}
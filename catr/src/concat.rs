@@ -0,0 +1,86 @@
+use crate::{open, read_buffered, CatrError, OpenOptions};
+use std::cell::Cell;
+use std::io::{self, BufRead, Read};
+use std::rc::Rc;
+use std::vec::IntoIter;
+
+/// Presents several inputs as one continuous stream for `--continuous`,
+/// opening each file only once the previous one is exhausted. Files that
+/// cannot be opened or read are reported, counted in `failed` and skipped,
+/// as in per-file mode. With `fail_fast` the stream ends at the first one.
+pub struct Concat {
+    files: IntoIter<String>,
+    current: Option<(String, Box<dyn BufRead>)>,
+    options: OpenOptions,
+    fail_fast: bool,
+    failed: Rc<Cell<usize>>,
+}
+
+impl Concat {
+    pub fn new(
+        files: Vec<String>,
+        options: OpenOptions,
+        fail_fast: bool,
+        failed: Rc<Cell<usize>>,
+    ) -> Self {
+        Concat {
+            files: files.into_iter(),
+            current: None,
+            options,
+            fail_fast,
+            failed,
+        }
+    }
+
+    fn fail(&mut self, err: CatrError) {
+        eprintln!("{}", err);
+        self.failed.set(self.failed.get() + 1);
+        self.current = None;
+        if self.fail_fast {
+            self.files = Vec::new().into_iter();
+        }
+    }
+}
+
+impl Read for Concat {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        read_buffered(self, buf)
+    }
+}
+
+impl BufRead for Concat {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        loop {
+            match &mut self.current {
+                None => match self.files.next() {
+                    None => return Ok(&[]),
+                    Some(filename) => match open(&filename, self.options) {
+                        Err(source) => self.fail(CatrError::Open {
+                            path: filename,
+                            source,
+                        }),
+                        Ok(file) => self.current = Some((filename, file)),
+                    },
+                },
+                Some((filename, file)) => match file.fill_buf() {
+                    Ok(buf) if !buf.is_empty() => break,
+                    Ok(_) => self.current = None,
+                    Err(source) => {
+                        let path = filename.clone();
+                        self.fail(CatrError::Read { path, source });
+                    }
+                },
+            }
+        }
+        match &mut self.current {
+            Some((_, file)) => file.fill_buf(),
+            None => Ok(&[]),
+        }
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if let Some((_, file)) = &mut self.current {
+            file.consume(amt);
+        }
+    }
+}
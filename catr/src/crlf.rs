@@ -0,0 +1,40 @@
+use std::str::FromStr;
+
+/// What to do with the carriage return of a CRLF line ending, selected
+/// with `--crlf`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Crlf {
+    /// Write line endings as they are.
+    #[default]
+    Keep,
+    /// End every line with a bare LF.
+    Strip,
+    /// End every line with CRLF.
+    Add,
+}
+
+impl FromStr for Crlf {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "keep" => Ok(Crlf::Keep),
+            "strip" => Ok(Crlf::Strip),
+            "add" => Ok(Crlf::Add),
+            _ => Err(format!("Invalid line ending mode \"{}\"", value)),
+        }
+    }
+}
+
+impl Crlf {
+    /// Splits the carriage return off a line whose newline has already
+    /// been removed, returning the line and whether its ending should
+    /// include a carriage return. Kept endings stay part of the line.
+    pub fn split(self, line: &[u8], has_newline: bool) -> (&[u8], bool) {
+        if self == Crlf::Keep || !has_newline {
+            return (line, false);
+        }
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        (line, self == Crlf::Add)
+    }
+}
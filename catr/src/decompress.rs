@@ -0,0 +1,49 @@
+use std::io::{self, BufRead, BufReader};
+
+/// Compression formats recognized by `--decompress`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+}
+
+impl Compression {
+    /// Identifies the format from the magic bytes at the start of a stream.
+    pub fn detect(header: &[u8]) -> Option<Self> {
+        if header.starts_with(&[0x1f, 0x8b]) {
+            Some(Compression::Gzip)
+        } else if header.starts_with(b"BZh") {
+            Some(Compression::Bzip2)
+        } else if header.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Some(Compression::Xz)
+        } else if header.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Compression::Zstd)
+        } else {
+            None
+        }
+    }
+}
+
+/// Wraps `file` in a decoder if it starts with a known compression header,
+/// otherwise returns it unchanged. Concatenated members and frames are all
+/// decoded, as `zcat` and friends do.
+pub fn decoder(mut file: Box<dyn BufRead>) -> io::Result<Box<dyn BufRead>> {
+    let reader: Box<dyn BufRead> = match Compression::detect(file.fill_buf()?) {
+        None => file,
+        Some(Compression::Gzip) => {
+            Box::new(BufReader::new(flate2::bufread::MultiGzDecoder::new(file)))
+        }
+        Some(Compression::Bzip2) => {
+            Box::new(BufReader::new(bzip2::bufread::MultiBzDecoder::new(file)))
+        }
+        Some(Compression::Xz) => Box::new(BufReader::new(
+            xz2::bufread::XzDecoder::new_multi_decoder(file),
+        )),
+        Some(Compression::Zstd) => Box::new(BufReader::new(
+            zstd::stream::read::Decoder::with_buffer(file)?,
+        )),
+    };
+    Ok(reader)
+}
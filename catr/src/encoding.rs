@@ -0,0 +1,102 @@
+use encoding_rs::{CoderResult, Decoder, EncoderResult, Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use encoding_rs_io::DecodeReaderBytesBuilder;
+use std::io::{self, BufRead, BufReader, Write};
+
+/// Looks up an encoding by any of its WHATWG labels, such as `utf-16le`,
+/// `latin1` or `shift_jis`.
+pub fn parse(label: &str) -> Result<&'static Encoding, String> {
+    Encoding::for_label(label.trim().as_bytes())
+        .ok_or_else(|| format!("Invalid encoding \"{}\"", label))
+}
+
+/// Transcodes `file` to UTF-8 from `encoding`, or from whatever a byte
+/// order mark names, which takes precedence and is removed. Without either
+/// the bytes pass through unchanged. Bytes that are invalid in the encoding
+/// become U+FFFD.
+pub fn decoder(file: Box<dyn BufRead>, encoding: Option<&'static Encoding>) -> Box<dyn BufRead> {
+    let reader = DecodeReaderBytesBuilder::new()
+        .encoding(encoding)
+        .bom_override(true)
+        .strip_bom(true)
+        .build(file);
+    Box::new(BufReader::new(reader))
+}
+
+/// Re-encodes the UTF-8 written to it as `encoding` for `--to-encoding`.
+/// Characters the encoding cannot represent are written as `?`.
+pub struct Encode<W: Write> {
+    inner: W,
+    encoding: &'static Encoding,
+    /// Carries UTF-8 sequences split across writes.
+    decoder: Decoder,
+    text: String,
+}
+
+impl<W: Write> Encode<W> {
+    pub fn new(inner: W, encoding: &'static Encoding) -> Self {
+        Encode {
+            inner,
+            encoding,
+            decoder: UTF_8.new_decoder_without_bom_handling(),
+            text: String::new(),
+        }
+    }
+
+    /// Writes out anything held back, such as a truncated final character,
+    /// and flushes.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.transcode(b"", true)?;
+        self.inner.flush()
+    }
+
+    fn transcode(&mut self, buf: &[u8], last: bool) -> io::Result<()> {
+        self.text.clear();
+        let needed = self
+            .decoder
+            .max_utf8_buffer_length(buf.len())
+            .unwrap_or(buf.len() * 3 + 4);
+        self.text.reserve(needed);
+        let (result, _, _) = self.decoder.decode_to_string(buf, &mut self.text, last);
+        debug_assert_eq!(result, CoderResult::InputEmpty);
+        let bytes = encode(&self.text, self.encoding);
+        self.inner.write_all(&bytes)
+    }
+}
+
+impl<W: Write> Write for Encode<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.transcode(buf, false)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn encode(text: &str, encoding: &'static Encoding) -> Vec<u8> {
+    // encoding_rs only decodes UTF-16, so it is encoded here.
+    if encoding == UTF_16LE {
+        return text.encode_utf16().flat_map(u16::to_le_bytes).collect();
+    }
+    if encoding == UTF_16BE {
+        return text.encode_utf16().flat_map(u16::to_be_bytes).collect();
+    }
+    let mut encoder = encoding.new_encoder();
+    let mut out = Vec::with_capacity(text.len());
+    let mut text = text;
+    loop {
+        let needed = encoder
+            .max_buffer_length_from_utf8_without_replacement(text.len())
+            .unwrap_or(text.len() * 4 + 16);
+        out.reserve(needed);
+        let (result, read) =
+            encoder.encode_from_utf8_to_vec_without_replacement(text, &mut out, true);
+        text = &text[read..];
+        match result {
+            EncoderResult::InputEmpty => return out,
+            EncoderResult::Unmappable(_) => out.push(b'?'),
+            EncoderResult::OutputFull => {}
+        }
+    }
+}
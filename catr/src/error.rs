@@ -0,0 +1,85 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Everything that can go wrong in catr.
+#[derive(Debug)]
+pub enum CatrError {
+    /// An invalid command-line argument.
+    Usage(String),
+    /// An input that could not be opened.
+    Open { path: String, source: io::Error },
+    /// An input that failed part way through being read.
+    Read { path: String, source: io::Error },
+    /// Writing the output failed.
+    Io(io::Error),
+    /// Some inputs failed. Each has already been reported, so this only
+    /// carries the count for the exit status.
+    Failed(usize),
+}
+
+impl CatrError {
+    /// Attributes an error from processing `path` to that input. Errors
+    /// other than I/O errors are returned unchanged.
+    pub fn read(path: &str, err: CatrError) -> Self {
+        match err {
+            CatrError::Io(source) => CatrError::Read {
+                path: path.to_string(),
+                source,
+            },
+            err => err,
+        }
+    }
+
+    /// Whether the reader on the other end of stdout or the pager went
+    /// away, which ends the output quietly rather than as a failure.
+    pub fn is_broken_pipe(&self) -> bool {
+        match self {
+            CatrError::Io(err) | CatrError::Read { source: err, .. } => {
+                err.kind() == io::ErrorKind::BrokenPipe
+            }
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for CatrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CatrError::Usage(message) => write!(f, "{}", message),
+            CatrError::Open { path, source } => write!(f, "Failed to open {}: {}", path, source),
+            CatrError::Read { path, source } => write!(f, "{}: {}", path, source),
+            CatrError::Io(err) => write!(f, "{}", err),
+            CatrError::Failed(1) => write!(f, "1 input could not be read"),
+            CatrError::Failed(count) => write!(f, "{} inputs could not be read", count),
+        }
+    }
+}
+
+impl Error for CatrError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CatrError::Open { source, .. } | CatrError::Read { source, .. } => Some(source),
+            CatrError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for CatrError {
+    fn from(err: io::Error) -> Self {
+        CatrError::Io(err)
+    }
+}
+
+impl From<String> for CatrError {
+    fn from(message: String) -> Self {
+        CatrError::Usage(message)
+    }
+}
+
+impl From<&str> for CatrError {
+    fn from(message: &str) -> Self {
+        CatrError::Usage(message.to_string())
+    }
+}
@@ -0,0 +1,109 @@
+use std::fs::{self, File, Metadata};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Reads a file like `tail -F`: at end of file it waits for more data
+/// instead of returning EOF. If the file shrinks it is read again from the
+/// start, and if another file replaces it under the same name, as log
+/// rotation does, the new file is opened.
+///
+/// A line still being written is not held back: when the data so far stops
+/// partway through a line, that is reported once as the end of the file,
+/// so the reader can show the line up to there before waiting for the rest.
+pub struct Follow {
+    path: PathBuf,
+    file: BufReader<File>,
+    id: Option<(u64, u64)>,
+    pos: u64,
+    /// Whether to report a pause partway through a line; a decoder that
+    /// takes the first end of file as final cannot be given one.
+    partial_lines: bool,
+    /// Whether the last byte read ended a line.
+    at_line_start: bool,
+    /// Whether the current pause has already been reported.
+    paused: bool,
+}
+
+#[cfg(unix)]
+fn file_id(meta: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_meta: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+impl Follow {
+    pub fn open(path: &str, partial_lines: bool) -> io::Result<Self> {
+        let file = crate::open_file(path)?;
+        let id = file_id(&file.metadata()?);
+        Ok(Follow {
+            path: PathBuf::from(path),
+            file: BufReader::new(file),
+            id,
+            pos: 0,
+            partial_lines,
+            at_line_start: true,
+            paused: false,
+        })
+    }
+
+    /// Called at end of file: reopens or rewinds the file if it was rotated
+    /// or truncated, otherwise waits before polling again.
+    fn check(&mut self) -> io::Result<()> {
+        match fs::metadata(&self.path) {
+            Ok(meta) if file_id(&meta) != self.id => {
+                if let Ok(file) = File::open(&self.path) {
+                    eprintln!("{}: file replaced, following new file", self.path.display());
+                    self.id = file_id(&file.metadata()?);
+                    self.file = BufReader::new(file);
+                    self.pos = 0;
+                    return Ok(());
+                }
+            }
+            Ok(meta) if meta.len() < self.pos => {
+                eprintln!("{}: file truncated", self.path.display());
+                self.file.seek(SeekFrom::Start(0))?;
+                self.pos = 0;
+                return Ok(());
+            }
+            _ => {}
+        }
+        thread::sleep(POLL_INTERVAL);
+        Ok(())
+    }
+}
+
+impl Read for Follow {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        crate::read_buffered(self, buf)
+    }
+}
+
+impl BufRead for Follow {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        while self.file.fill_buf()?.is_empty() {
+            if self.partial_lines && !self.at_line_start && !self.paused {
+                self.paused = true;
+                return Ok(&[]);
+            }
+            self.check()?;
+        }
+        self.file.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if let Some(&last) = self.file.buffer()[..amt].last() {
+            self.at_line_start = last == b'\n';
+            self.paused = false;
+        }
+        self.file.consume(amt);
+        self.pos += amt as u64;
+    }
+}
@@ -0,0 +1,60 @@
+use crate::MyResult;
+use std::io::Write;
+use std::str::FromStr;
+
+/// The banner printed before each file by `--headers`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Headers {
+    /// `==> path <==` as printed by `head` and `tail`, or a box when
+    /// colors are enabled.
+    #[default]
+    Plain,
+    /// A JSON object on a line of its own naming the file that follows.
+    Json,
+}
+
+impl FromStr for Headers {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "plain" => Ok(Headers::Plain),
+            "json" => Ok(Headers::Json),
+            _ => Err(format!("Invalid headers format \"{}\"", value)),
+        }
+    }
+}
+
+impl Headers {
+    /// Writes the header for the `index`th file read. Plain headers after
+    /// the first are separated from the previous file by a blank line.
+    pub fn write<W: Write>(
+        &self,
+        out: &mut W,
+        filename: &str,
+        index: usize,
+        color: bool,
+    ) -> MyResult<()> {
+        let name = match filename {
+            "-" => "standard input",
+            name => name,
+        };
+        if *self == Headers::Json {
+            let header = serde_json::json!({ "file": name, "index": index });
+            writeln!(out, "{}", header)?;
+            return Ok(());
+        }
+        if index > 0 {
+            writeln!(out)?;
+        }
+        if color {
+            let rule = "─".repeat(name.chars().count() + 2);
+            writeln!(out, "┌{}┐", rule)?;
+            writeln!(out, "│ \x1b[1m{}\x1b[0m │", name)?;
+            writeln!(out, "└{}┘", rule)?;
+        } else {
+            writeln!(out, "==> {} <==", name)?;
+        }
+        Ok(())
+    }
+}
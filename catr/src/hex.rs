@@ -0,0 +1,112 @@
+use crate::{LineRanges, MyResult};
+use std::io::{BufRead, Cursor, Read, Write};
+
+/// The most bytes per row `--hex-width` accepts, as with `xxd -c`.
+pub const MAX_WIDTH: usize = 256;
+
+/// Layout of a `--hex` dump: bytes per row and bytes per space-separated
+/// group, as with `xxd -c` and `xxd -g`. A group size of 0 disables grouping.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HexFormat {
+    pub width: usize,
+    pub group: usize,
+}
+
+impl Default for HexFormat {
+    fn default() -> Self {
+        HexFormat {
+            width: 16,
+            group: 2,
+        }
+    }
+}
+
+impl HexFormat {
+    fn hex_columns(&self) -> usize {
+        let groups = match self.group {
+            0 => 1,
+            group => self.width.div_ceil(group),
+        };
+        self.width * 2 + groups - 1
+    }
+
+    fn write_row<W: Write>(&self, out: &mut W, offset: usize, row: &[u8]) -> MyResult<()> {
+        let mut hex = String::with_capacity(self.hex_columns());
+        for (i, byte) in row.iter().enumerate() {
+            if i > 0 && self.group > 0 && i % self.group == 0 {
+                hex.push(' ');
+            }
+            hex.push_str(&format!("{:02x}", byte));
+        }
+        let ascii: String = row
+            .iter()
+            .map(|&b| {
+                if (0x20..0x7f).contains(&b) {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        writeln!(
+            out,
+            "{:08x}: {:<width$}  {}",
+            offset,
+            hex,
+            ascii,
+            width = self.hex_columns()
+        )?;
+        Ok(())
+    }
+}
+
+/// Writes `file` as offset/hex/ASCII rows. With `ranges`, only the selected
+/// bytes are dumped; the range positions are 0-based byte offsets, and a
+/// new row starts wherever the selection skips ahead.
+pub fn dump<W: Write>(
+    mut file: Box<dyn BufRead>,
+    format: HexFormat,
+    ranges: Option<&LineRanges>,
+    mut out: W,
+) -> MyResult<()> {
+    let mut len = None;
+    if ranges.is_some_and(LineRanges::needs_total) {
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+        len = Some(data.len());
+        file = Box::new(Cursor::new(data));
+    }
+    let width = format.width.max(1);
+    let mut row = Vec::with_capacity(width);
+    let mut row_start = 0;
+    let mut offset = 0;
+    loop {
+        let chunk = file.fill_buf()?;
+        if chunk.is_empty() {
+            break;
+        }
+        for &byte in chunk {
+            if ranges.is_none_or(|ranges| ranges.contains_offset(offset, len)) {
+                if !row.is_empty() && row_start + row.len() != offset {
+                    format.write_row(&mut out, row_start, &row)?;
+                    row.clear();
+                }
+                if row.is_empty() {
+                    row_start = offset;
+                }
+                row.push(byte);
+                if row.len() == width {
+                    format.write_row(&mut out, row_start, &row)?;
+                    row.clear();
+                }
+            }
+            offset += 1;
+        }
+        let consumed = chunk.len();
+        file.consume(consumed);
+    }
+    if !row.is_empty() {
+        format.write_row(&mut out, row_start, &row)?;
+    }
+    Ok(())
+}
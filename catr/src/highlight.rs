@@ -0,0 +1,502 @@
+use regex::bytes::Regex;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Languages understood by `--highlight`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Rust,
+    Toml,
+    Json,
+}
+
+impl Language {
+    /// Picks a language from a file's extension, looking through a
+    /// compression suffix such as `.gz` so `Cargo.toml.gz` is still TOML.
+    pub fn from_path(path: &str) -> Option<Self> {
+        let path = Path::new(path);
+        let ext = path.extension()?.to_str()?;
+        match ext {
+            "rs" => Some(Language::Rust),
+            "toml" => Some(Language::Toml),
+            "json" | "jsonl" | "ndjson" => Some(Language::Json),
+            "gz" | "bz2" | "xz" | "zst" => Self::from_path(path.file_stem()?.to_str()?),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Language {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "rust" | "rs" => Ok(Language::Rust),
+            "toml" => Ok(Language::Toml),
+            "json" => Ok(Language::Json),
+            _ => Err(format!("Unknown highlight language \"{}\"", value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Style {
+    Comment,
+    String,
+    Number,
+    Keyword,
+    Literal,
+    Macro,
+    Key,
+    Section,
+}
+
+impl Style {
+    fn code(self) -> &'static [u8] {
+        match self {
+            Style::Comment => b"\x1b[90m",
+            Style::String => b"\x1b[32m",
+            Style::Number => b"\x1b[33m",
+            Style::Keyword => b"\x1b[35m",
+            Style::Literal => b"\x1b[36m",
+            Style::Macro => b"\x1b[34m",
+            Style::Key => b"\x1b[34m",
+            Style::Section => b"\x1b[1;34m",
+        }
+    }
+}
+
+const RESET: &[u8] = b"\x1b[0m";
+const MATCH: &[u8] = b"\x1b[1;31m";
+
+const RUST_KEYWORDS: &[&[u8]] = &[
+    b"as",
+    b"async",
+    b"await",
+    b"break",
+    b"const",
+    b"continue",
+    b"crate",
+    b"dyn",
+    b"else",
+    b"enum",
+    b"extern",
+    b"fn",
+    b"for",
+    b"if",
+    b"impl",
+    b"in",
+    b"let",
+    b"loop",
+    b"match",
+    b"mod",
+    b"move",
+    b"mut",
+    b"pub",
+    b"ref",
+    b"return",
+    b"self",
+    b"Self",
+    b"static",
+    b"struct",
+    b"super",
+    b"trait",
+    b"type",
+    b"unsafe",
+    b"use",
+    b"where",
+    b"while",
+];
+
+/// Where the previous line left off, for constructs spanning lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Normal,
+    BlockComment(usize),
+    String,
+    RawString(usize),
+    MultiString(u8),
+}
+
+/// Colors lines of source with ANSI escapes. One highlighter is used per
+/// input so block comments and multi-line strings carry across lines.
+pub struct Highlighter {
+    language: Language,
+    state: State,
+}
+
+fn paint(out: &mut Vec<u8>, style: Style, text: &[u8]) {
+    if !text.is_empty() {
+        out.extend_from_slice(style.code());
+        out.extend_from_slice(text);
+        out.extend_from_slice(RESET);
+    }
+}
+
+fn is_ident(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_'
+}
+
+fn word_end(line: &[u8], start: usize) -> usize {
+    start + line[start..].iter().take_while(|&&b| is_ident(b)).count()
+}
+
+fn number_end(line: &[u8], start: usize, extra: &[u8]) -> usize {
+    let mut i = start + 1;
+    while i < line.len() {
+        let byte = line[i];
+        let decimal_point = byte == b'.' && line.get(i + 1).is_some_and(u8::is_ascii_digit);
+        if is_ident(byte) || decimal_point || extra.contains(&byte) {
+            i += 1;
+        } else {
+            break;
+        }
+    }
+    i
+}
+
+/// Scans a quoted string whose opening quote is just before `start`,
+/// returning the index after the closing quote and whether one was found.
+fn quoted_end(line: &[u8], start: usize, quote: u8, escapes: bool) -> (usize, bool) {
+    let mut i = start;
+    while i < line.len() {
+        if escapes && line[i] == b'\\' {
+            i += 2;
+        } else if line[i] == quote {
+            return (i + 1, true);
+        } else {
+            i += 1;
+        }
+    }
+    (line.len(), false)
+}
+
+fn find(line: &[u8], start: usize, pattern: &[u8]) -> Option<usize> {
+    line.get(start..)?
+        .windows(pattern.len())
+        .position(|w| w == pattern)
+        .map(|pos| start + pos)
+}
+
+impl Highlighter {
+    pub fn new(language: Language) -> Self {
+        Highlighter {
+            language,
+            state: State::Normal,
+        }
+    }
+
+    pub fn highlight(&mut self, line: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(line.len() * 2);
+        let start = self.resume(line, &mut out);
+        match self.language {
+            Language::Rust => self.rust(line, start, &mut out),
+            Language::Toml => self.toml(line, start, &mut out),
+            Language::Json => self.json(line, start, &mut out),
+        }
+        out
+    }
+
+    /// Finishes a construct left open by the previous line, returning the
+    /// index where normal scanning picks up.
+    fn resume(&mut self, line: &[u8], out: &mut Vec<u8>) -> usize {
+        let style = match self.state {
+            State::Normal => return 0,
+            State::BlockComment(_) => Style::Comment,
+            _ => Style::String,
+        };
+        let end = match self.state {
+            State::BlockComment(depth) => self.block_comment(line, 0, depth),
+            State::RawString(hashes) => self.raw_string(line, 0, hashes),
+            State::MultiString(quote) => self.multi_string(line, 0, quote),
+            _ => self.string(line, 0),
+        };
+        paint(out, style, &line[..end]);
+        end
+    }
+
+    fn block_comment(&mut self, line: &[u8], start: usize, mut depth: usize) -> usize {
+        let mut i = start;
+        while i < line.len() {
+            if line[i..].starts_with(b"/*") {
+                depth += 1;
+                i += 2;
+            } else if line[i..].starts_with(b"*/") {
+                depth -= 1;
+                i += 2;
+                if depth == 0 {
+                    self.state = State::Normal;
+                    return i;
+                }
+            } else {
+                i += 1;
+            }
+        }
+        self.state = State::BlockComment(depth);
+        i
+    }
+
+    fn string(&mut self, line: &[u8], start: usize) -> usize {
+        let (end, closed) = quoted_end(line, start, b'"', true);
+        self.state = if closed { State::Normal } else { State::String };
+        end
+    }
+
+    fn raw_string(&mut self, line: &[u8], start: usize, hashes: usize) -> usize {
+        let mut closing = vec![b'"'];
+        closing.resize(hashes + 1, b'#');
+        match find(line, start, &closing) {
+            Some(pos) => {
+                self.state = State::Normal;
+                pos + closing.len()
+            }
+            None => {
+                self.state = State::RawString(hashes);
+                line.len()
+            }
+        }
+    }
+
+    fn multi_string(&mut self, line: &[u8], start: usize, quote: u8) -> usize {
+        match find(line, start, &[quote; 3]) {
+            Some(pos) => {
+                self.state = State::Normal;
+                pos + 3
+            }
+            None => {
+                self.state = State::MultiString(quote);
+                line.len()
+            }
+        }
+    }
+
+    fn rust(&mut self, line: &[u8], mut i: usize, out: &mut Vec<u8>) {
+        while i < line.len() {
+            let byte = line[i];
+            let rest = &line[i..];
+            if rest.starts_with(b"//") {
+                paint(out, Style::Comment, rest);
+                return;
+            } else if rest.starts_with(b"/*") {
+                let end = self.block_comment(line, i, 0);
+                paint(out, Style::Comment, &line[i..end]);
+                i = end;
+            } else if byte == b'"' {
+                let end = self.string(line, i + 1);
+                paint(out, Style::String, &line[i..end]);
+                i = end;
+            } else if byte == b'\'' {
+                i = self.rust_quote(line, i, out);
+            } else if byte.is_ascii_digit() {
+                let end = number_end(line, i, b"");
+                paint(out, Style::Number, &line[i..end]);
+                i = end;
+            } else if is_ident(byte) {
+                i = self.rust_word(line, i, out);
+            } else if rest.starts_with(b"#[") || rest.starts_with(b"#![") {
+                let mut depth = 0;
+                let mut end = i;
+                for (pos, &b) in line.iter().enumerate().skip(i) {
+                    end = pos + 1;
+                    match b {
+                        b'[' => depth += 1,
+                        b']' if depth == 1 => break,
+                        b']' => depth -= 1,
+                        _ => {}
+                    }
+                }
+                paint(out, Style::Macro, &line[i..end]);
+                i = end;
+            } else {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+
+    /// A `'` starts either a character literal or a lifetime.
+    fn rust_quote(&mut self, line: &[u8], i: usize, out: &mut Vec<u8>) -> usize {
+        let char_len = match line.get(i + 1) {
+            Some(b'\\') => quoted_end(line, i + 1, b'\'', true).0 - i,
+            Some(&b) if b >= 0xf0 => 6,
+            Some(&b) if b >= 0xe0 => 5,
+            Some(&b) if b >= 0xc0 => 4,
+            Some(_) => 3,
+            None => 1,
+        };
+        if line.get(i + char_len - 1) == Some(&b'\'') && char_len > 2 {
+            paint(out, Style::String, &line[i..i + char_len]);
+            i + char_len
+        } else {
+            let end = word_end(line, i + 1);
+            paint(out, Style::Literal, &line[i..end]);
+            end
+        }
+    }
+
+    fn rust_word(&mut self, line: &[u8], i: usize, out: &mut Vec<u8>) -> usize {
+        let end = word_end(line, i);
+        let word = &line[i..end];
+        let prefix = matches!(word, b"b" | b"r" | b"br" | b"c" | b"cr");
+        if prefix && word.ends_with(b"r") {
+            let hashes = line[end..].iter().take_while(|&&b| b == b'#').count();
+            if line.get(end + hashes) == Some(&b'"') {
+                let close = self.raw_string(line, end + hashes + 1, hashes);
+                paint(out, Style::String, &line[i..close]);
+                return close;
+            }
+        }
+        if prefix && line.get(end) == Some(&b'"') {
+            let close = self.string(line, end + 1);
+            paint(out, Style::String, &line[i..close]);
+            return close;
+        }
+        if prefix && word == b"b" && line.get(end) == Some(&b'\'') {
+            let close = quoted_end(line, end + 1, b'\'', true).0;
+            paint(out, Style::String, &line[i..close]);
+            return close;
+        }
+        if word == b"true" || word == b"false" {
+            paint(out, Style::Literal, word);
+        } else if RUST_KEYWORDS.contains(&word) {
+            paint(out, Style::Keyword, word);
+        } else if line.get(end) == Some(&b'!') && line.get(end + 1) != Some(&b'=') {
+            paint(out, Style::Macro, &line[i..end + 1]);
+            return end + 1;
+        } else if word[0].is_ascii_uppercase() {
+            paint(out, Style::Literal, word);
+        } else {
+            out.extend_from_slice(word);
+        }
+        end
+    }
+
+    fn toml(&mut self, line: &[u8], mut i: usize, out: &mut Vec<u8>) {
+        if i == 0 {
+            let indent = line.iter().take_while(|b| b.is_ascii_whitespace()).count();
+            out.extend_from_slice(&line[..indent]);
+            i = indent;
+            if line.get(i) == Some(&b'[') {
+                let end = find(line, i, b"]")
+                    .map(|pos| {
+                        if line.get(pos + 1) == Some(&b']') {
+                            pos + 2
+                        } else {
+                            pos + 1
+                        }
+                    })
+                    .unwrap_or(line.len());
+                paint(out, Style::Section, &line[i..end]);
+                i = end;
+            } else if let Some(eq) = toml_key_end(line, i) {
+                let key = &line[i..eq];
+                let trimmed = key.len()
+                    - key
+                        .iter()
+                        .rev()
+                        .take_while(|b| b.is_ascii_whitespace())
+                        .count();
+                paint(out, Style::Key, &key[..trimmed]);
+                out.extend_from_slice(&key[trimmed..]);
+                i = eq;
+            }
+        }
+        while i < line.len() {
+            let byte = line[i];
+            let rest = &line[i..];
+            if byte == b'#' {
+                paint(out, Style::Comment, rest);
+                return;
+            } else if rest.starts_with(b"\"\"\"") || rest.starts_with(b"'''") {
+                let end = self.multi_string(line, i + 3, byte);
+                paint(out, Style::String, &line[i..end]);
+                i = end;
+            } else if byte == b'"' || byte == b'\'' {
+                let end = quoted_end(line, i + 1, byte, byte == b'"').0;
+                paint(out, Style::String, &line[i..end]);
+                i = end;
+            } else if byte.is_ascii_digit()
+                || ((byte == b'+' || byte == b'-')
+                    && line.get(i + 1).is_some_and(u8::is_ascii_digit))
+            {
+                let end = number_end(line, i, b"-:+");
+                paint(out, Style::Number, &line[i..end]);
+                i = end;
+            } else if is_ident(byte) {
+                let end = word_end(line, i);
+                let word = &line[i..end];
+                match word {
+                    b"true" | b"false" => paint(out, Style::Literal, word),
+                    b"inf" | b"nan" => paint(out, Style::Number, word),
+                    _ => out.extend_from_slice(word),
+                }
+                i = end;
+            } else {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+
+    fn json(&mut self, line: &[u8], mut i: usize, out: &mut Vec<u8>) {
+        while i < line.len() {
+            let byte = line[i];
+            if byte == b'"' {
+                let end = quoted_end(line, i + 1, b'"', true).0;
+                let next = line[end..].iter().find(|b| !b.is_ascii_whitespace());
+                let style = if next == Some(&b':') {
+                    Style::Key
+                } else {
+                    Style::String
+                };
+                paint(out, style, &line[i..end]);
+                i = end;
+            } else if byte.is_ascii_digit() || byte == b'-' {
+                let end = number_end(line, i, b"+-");
+                paint(out, Style::Number, &line[i..end]);
+                i = end;
+            } else if is_ident(byte) {
+                let end = word_end(line, i);
+                let word = &line[i..end];
+                match word {
+                    b"true" | b"false" | b"null" => paint(out, Style::Literal, word),
+                    _ => out.extend_from_slice(word),
+                }
+                i = end;
+            } else {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+}
+
+/// Finds the `=` ending a TOML key, ignoring any inside quoted keys.
+fn toml_key_end(line: &[u8], start: usize) -> Option<usize> {
+    let mut i = start;
+    while i < line.len() {
+        match line[i] {
+            b'=' => return Some(i),
+            b'#' => return None,
+            quote @ (b'"' | b'\'') => i = quoted_end(line, i + 1, quote, quote == b'"').0,
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// Colors every non-empty match of `pattern` in `line`, for
+/// `--highlight-pattern`.
+pub fn highlight_matches(pattern: &Regex, line: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(line.len() + 16);
+    let mut last = 0;
+    for found in pattern.find_iter(line).filter(|m| !m.is_empty()) {
+        out.extend_from_slice(&line[last..found.start()]);
+        out.extend_from_slice(MATCH);
+        out.extend_from_slice(found.as_bytes());
+        out.extend_from_slice(RESET);
+        last = found.end();
+    }
+    out.extend_from_slice(&line[last..]);
+    out
+}
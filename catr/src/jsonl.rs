@@ -0,0 +1,47 @@
+use crate::MyResult;
+use std::io::Write;
+use std::str::FromStr;
+
+/// How selected lines are written, chosen with `--output`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    /// One JSON object per line, for feeding other tools.
+    Jsonl,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "text" => Ok(OutputFormat::Text),
+            "jsonl" => Ok(OutputFormat::Jsonl),
+            _ => Err(format!("Invalid output format \"{}\"", value)),
+        }
+    }
+}
+
+/// Writes one line as a JSON object: the `file` it came from, its 1-based
+/// `line` number in that file, the `number` `-n` or `-b` would print for
+/// it, the byte `offset` of its start and its `text` without the newline.
+/// Text that is not valid UTF-8 is converted lossily.
+pub fn write_line<W: Write>(
+    out: &mut W,
+    file: Option<&str>,
+    line: usize,
+    number: Option<usize>,
+    offset: usize,
+    text: &[u8],
+) -> MyResult<()> {
+    let record = serde_json::json!({
+        "file": file,
+        "line": line,
+        "number": number,
+        "offset": offset,
+        "text": String::from_utf8_lossy(text),
+    });
+    writeln!(out, "{}", record)?;
+    Ok(())
+}
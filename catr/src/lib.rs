@@ -1,73 +1,499 @@
+mod columns;
+mod concat;
+mod crlf;
+mod decompress;
+mod encoding;
+mod error;
+mod follow;
+mod headers;
+mod hex;
+mod highlight;
+mod jsonl;
+mod number;
+mod pager;
+mod prefetch;
+mod range;
+mod walk;
+
 use clap::{App, Arg};
-use std::error::Error;
+pub use crlf::Crlf;
+use encoding_rs::{Encoding, UTF_8};
+pub use error::CatrError;
+pub use headers::Headers;
+pub use hex::HexFormat;
+pub use highlight::{Highlighter, Language};
+pub use jsonl::OutputFormat;
+pub use number::{Justify, NumberFormat};
+pub use pager::Paging;
+pub use range::{LineIndex, LineRange, LineRanges};
+use regex::bytes::Regex;
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::VecDeque;
 use std::fs::File;
-use std::io::{self, BufRead, BufReader};
+use std::io::{self, BufRead, BufReader, BufWriter, IsTerminal, Read, Seek, SeekFrom, Write};
+use std::mem;
+use std::rc::Rc;
 
-type MyResult<T> = Result<T, Box<dyn Error>>;
+type MyResult<T> = Result<T, CatrError>;
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct Config {
-    files: Vec<String>,
-    number_lines: bool,
-    number_nonblank_lines: bool,
-    line_range: Option<(usize, usize)>,
-    non_printing: bool,
+    pub files: Vec<String>,
+    pub number_lines: bool,
+    pub number_nonblank_lines: bool,
+    pub number_format: NumberFormat,
+    pub line_range: Option<LineRanges>,
+    pub non_printing: bool,
+    pub show_ends: bool,
+    pub show_tabs: bool,
+    pub squeeze_blank: bool,
+    pub continuous: bool,
+    pub paging: Paging,
+    pub decompress: bool,
+    pub highlight: bool,
+    pub language: Option<Language>,
+    pub color: bool,
+    pub hex: Option<HexFormat>,
+    pub follow: bool,
+    pub pattern: Option<Regex>,
+    pub invert_match: bool,
+    pub before_context: usize,
+    pub after_context: usize,
+    pub highlight_pattern: Option<Regex>,
+    pub recursive: bool,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub headers: Option<Headers>,
+    pub fail_fast: bool,
+    pub output: OutputFormat,
+    pub jobs: usize,
+    pub from_encoding: Option<&'static Encoding>,
+    pub to_encoding: Option<&'static Encoding>,
+    pub strip_bom: bool,
+    pub crlf: Crlf,
+    pub show_cr: bool,
+    pub expand_tabs: Option<usize>,
+    pub wrap: Option<usize>,
+    pub truncate: Option<usize>,
 }
 
 impl Config {
-    pub fn new(
-        files: Vec<String>,
-        number_lines: bool,
-        number_nonblank_lines: bool,
-        line_range: Option<(usize, usize)>,
-        non_printing: bool,
-    ) -> Self {
+    pub fn new(files: Vec<String>) -> Self {
         Config {
             files,
-            number_lines,
-            number_nonblank_lines,
-            line_range,
-            non_printing,
+            ..Default::default()
         }
     }
-    fn fmt_number_lines(&self, line: &String, line_number: usize) -> String {
-        format!("{:6}\t{}", line_number + 1, line)
+    fn fmt_number_lines(&self, line: &[u8], line_number: usize) -> Vec<u8> {
+        let mut out = self.number_format.format(line_number).into_bytes();
+        out.extend_from_slice(line);
+        out
     }
-    fn fmt_number_nonblank_lines(&self, line: &String, last_number: &mut u32) -> String {
+    fn fmt_number_nonblank_lines(&self, line: &[u8], last_number: &mut u32) -> Vec<u8> {
         if !line.is_empty() {
             *last_number += 1;
-            format!("{:6}\t{}", last_number, line)
+            let mut out = self
+                .number_format
+                .format(*last_number as usize - 1)
+                .into_bytes();
+            out.extend_from_slice(line);
+            out
+        } else {
+            Vec::new()
+        }
+    }
+    fn fmt_non_printing(&self, line: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(line.len());
+        for &byte in line {
+            if byte == b'\t' {
+                out.extend_from_slice(if self.show_tabs { b"^I" } else { b"\t" });
+                continue;
+            }
+            if byte == b'\r' && self.show_cr {
+                out.extend_from_slice(b"^M");
+                continue;
+            }
+            if !self.non_printing {
+                out.push(byte);
+                continue;
+            }
+            let mut byte = byte;
+            if byte >= 128 {
+                out.extend_from_slice(b"M-");
+                byte -= 128;
+            }
+            match byte {
+                0..=31 => out.extend_from_slice(&[b'^', byte + 64]),
+                127 => out.extend_from_slice(b"^?"),
+                _ => out.push(byte),
+            }
+        }
+        out
+    }
+
+    /// The number of lines `--tail` selects, when nothing else needs the
+    /// lines before them, so a file can be read from the end instead.
+    fn seekable_tail(&self) -> Option<usize> {
+        if self.number_lines
+            || self.number_nonblank_lines
+            || self.decompress
+            || self.transcode()
+            || self.hex.is_some()
+            || self.output == OutputFormat::Jsonl
+            // The highlighter has to see the earlier lines to know whether
+            // the tail starts inside a comment or string.
+            || (self.highlight && self.color)
+        {
+            return None;
+        }
+        self.line_range.as_ref().and_then(LineRanges::tail_lines)
+    }
+
+    /// Whether inputs are converted to UTF-8 as they are read, which also
+    /// removes any byte order mark.
+    fn transcode(&self) -> bool {
+        self.from_encoding.is_some() || self.to_encoding.is_some() || self.strip_bom
+    }
+
+    fn open_options(&self) -> OpenOptions {
+        OpenOptions {
+            decompress: self.decompress,
+            transcode: self.transcode(),
+            encoding: self.from_encoding,
+        }
+    }
+
+    /// Whether inputs are copied through untouched, so their bytes can go
+    /// straight to the output without being split into lines.
+    fn is_plain(&self) -> bool {
+        !(self.number_lines
+            || self.number_nonblank_lines
+            || self.non_printing
+            || self.show_ends
+            || self.show_tabs
+            || self.show_cr
+            || self.crlf != Crlf::Keep
+            || self.squeeze_blank
+            || self.decompress
+            || self.transcode()
+            || self.follow
+            || self.highlight && self.color
+            || self.highlight_pattern.is_some() && self.color)
+            && self.line_range.is_none()
+            && self.expand_tabs.is_none()
+            && self.wrap.is_none()
+            && self.truncate.is_none()
+            && self.hex.is_none()
+            && self.pattern.is_none()
+            && self.output == OutputFormat::Text
+    }
+
+    /// What `--wrap` starts continuation rows with: an arrow in place of
+    /// the line number, so the text stays aligned with the row above.
+    fn continuation_marker(&self) -> Vec<u8> {
+        if self.number_lines || self.number_nonblank_lines {
+            let format = &self.number_format;
+            format!("{:>width$}{}", "↪", format.separator, width = format.width).into_bytes()
         } else {
-            String::new()
+            "↪ ".as_bytes().to_vec()
         }
     }
 
-    pub fn process_flags(&self, file: Box<dyn BufRead>) {
-        let mut last_num = 0;
-        for (line_num, line_result) in file.lines().enumerate() {
-            if let Some((start, end)) = self.line_range {
-                if (line_num + 1) < start || (line_num + 1) > end {
-                    continue;
+    fn highlighter(&self, name: Option<&str>) -> Option<Highlighter> {
+        if !self.highlight || !self.color {
+            return None;
+        }
+        self.language
+            .or_else(|| name.and_then(Language::from_path))
+            .map(Highlighter::new)
+    }
+
+    pub fn process_flags(&self, file: Box<dyn BufRead>) -> MyResult<()> {
+        let mut out = BufWriter::new(io::stdout().lock());
+        self.process_flags_to(file, &mut out)?;
+        out.flush()?;
+        Ok(())
+    }
+
+    pub fn process_flags_to<W: Write>(&self, file: Box<dyn BufRead>, out: W) -> MyResult<()> {
+        self.process_input(file, None, out)
+    }
+
+    /// Formats one input. `name` is the file the input came from, if any,
+    /// and is used to pick a language for `--highlight`.
+    fn process_input<W: Write>(
+        &self,
+        mut file: Box<dyn BufRead>,
+        name: Option<&str>,
+        mut out: W,
+    ) -> MyResult<()> {
+        if let Some(format) = self.hex {
+            return hex::dump(file, format, self.line_range.as_ref(), out);
+        }
+        let ranges = self.line_range.as_ref();
+        let window = ranges.map_or(0, LineRanges::window);
+        let last_line = ranges.and_then(LineRanges::last_line);
+        let mut lines = Lines::new(self, name);
+        // Lines whose selection depends on how many lines follow them are
+        // held back until enough of the input has been read. The window can
+        // be far larger than the input, so the buffer only grows as needed.
+        let mut held: VecDeque<(usize, Vec<u8>)> = VecDeque::new();
+        let mut buf = Vec::new();
+        let mut line_num = 0;
+        // Whether the last line read so far has no newline yet. Only a
+        // followed file has more to read after that: the rest of the line.
+        let mut partial = false;
+        while partial || last_line.is_none_or(|last| line_num < last) {
+            buf.clear();
+            if file.read_until(b'\n', &mut buf)? == 0 {
+                break;
+            }
+            if partial {
+                partial = !buf.ends_with(b"\n");
+                lines.write_rest(&mut out, line_num, &buf)?;
+                continue;
+            }
+            partial = !buf.ends_with(b"\n");
+            line_num += 1;
+            if window == 0 {
+                let selected = ranges.is_none_or(|ranges| ranges.contains(line_num, None));
+                lines.write(&mut out, line_num, &buf, selected)?;
+                continue;
+            }
+            held.push_back((line_num, mem::take(&mut buf)));
+            if held.len() > window {
+                let (num, line) = held.pop_front().unwrap();
+                let selected = ranges.is_none_or(|ranges| ranges.contains(num, None));
+                lines.write(&mut out, num, &line, selected)?;
+                buf = line;
+            }
+        }
+        for (num, line) in held {
+            let selected = ranges.is_none_or(|ranges| ranges.contains(num, Some(line_num)));
+            lines.write(&mut out, num, &line, selected)?;
+        }
+        Ok(())
+    }
+}
+
+/// Formatting state carried from one line of an input to the next.
+struct Lines<'a> {
+    config: &'a Config,
+    name: Option<&'a str>,
+    highlighter: Option<Highlighter>,
+    last_num: u32,
+    prev_blank: bool,
+    squeezed: usize,
+    /// Byte offset of the next line in the input.
+    offset: usize,
+    /// Unmatched lines kept for `--before-context`, with their offsets.
+    before: VecDeque<(usize, usize, Vec<u8>)>,
+    /// Lines still to print for `--after-context`.
+    after: usize,
+    last_written: Option<usize>,
+}
+
+/// Splits the trailing newline off a line as read.
+fn split_newline(buf: &[u8]) -> (&[u8], bool) {
+    match buf.strip_suffix(b"\n") {
+        Some(line) => (line, true),
+        None => (buf, false),
+    }
+}
+
+impl<'a> Lines<'a> {
+    fn new(config: &'a Config, name: Option<&'a str>) -> Self {
+        Lines {
+            config,
+            name,
+            highlighter: config.highlighter(name),
+            last_num: 0,
+            prev_blank: false,
+            squeezed: 0,
+            offset: 0,
+            before: VecDeque::new(),
+            after: 0,
+            last_written: None,
+        }
+    }
+
+    /// Writes line `line_num` (1-based) if it is `selected` by the line
+    /// ranges and passes `--match`, or is context for a line that does.
+    /// `buf` holds the line as read, including its newline if it has one.
+    fn write<W: Write>(
+        &mut self,
+        out: &mut W,
+        line_num: usize,
+        buf: &[u8],
+        selected: bool,
+    ) -> MyResult<()> {
+        let config = self.config;
+        let offset = self.offset;
+        self.offset += buf.len();
+        let pattern = match &config.pattern {
+            Some(pattern) if selected => pattern,
+            _ if selected => return self.emit(out, line_num, offset, buf),
+            _ => {
+                self.skip(buf);
+                return Ok(());
+            }
+        };
+        let (line, _) = split_newline(buf);
+        if pattern.is_match(line) != config.invert_match {
+            let first = self.before.front().map_or(line_num, |(num, _, _)| *num);
+            let context = config.before_context > 0 || config.after_context > 0;
+            if context
+                && config.output == OutputFormat::Text
+                && self.last_written.is_some_and(|last| first > last + 1)
+            {
+                out.write_all(b"--\n")?;
+            }
+            while let Some((num, offset, line)) = self.before.pop_front() {
+                self.emit(out, num, offset, &line)?;
+            }
+            self.after = config.after_context;
+            self.emit(out, line_num, offset, buf)
+        } else if self.after > 0 {
+            self.after -= 1;
+            self.emit(out, line_num, offset, buf)
+        } else if config.before_context > 0 {
+            if self.before.len() == config.before_context {
+                if let Some((_, _, line)) = self.before.pop_front() {
+                    self.skip(&line);
                 }
             }
-            let line = line_result.unwrap();
-            if self.number_lines {
-                println!(
-                    "{}{}",
-                    self.fmt_number_lines(&line, line_num).as_str(),
-                    if self.non_printing { "$" } else { "" }
-                )
-            } else if self.number_nonblank_lines {
-                println!(
-                    "{}{}",
-                    self.fmt_number_nonblank_lines(&line, &mut last_num)
-                        .as_str(),
-                    if self.non_printing { "$" } else { "" }
-                );
+            self.before.push_back((line_num, offset, buf.to_vec()));
+            Ok(())
+        } else {
+            self.skip(buf);
+            Ok(())
+        }
+    }
+
+    /// Writes more of line `line_num`, the start of which was read before
+    /// the rest had been written to a followed file. The rest goes wherever
+    /// the start went, without another line number.
+    fn write_rest<W: Write>(&mut self, out: &mut W, line_num: usize, buf: &[u8]) -> MyResult<()> {
+        let offset = self.offset;
+        self.offset += buf.len();
+        if self.last_written == Some(line_num) {
+            self.emit_part(out, line_num, offset, buf, true)
+        } else {
+            self.skip(buf);
+            Ok(())
+        }
+    }
+
+    /// Passes over a line that is not written.
+    fn skip(&mut self, buf: &[u8]) {
+        // Keep the highlighter's view of comments and strings that span
+        // lines that are not shown.
+        if let Some(highlighter) = &mut self.highlighter {
+            highlighter.highlight(split_newline(buf).0);
+        }
+    }
+
+    /// Formats and writes a line that starts `offset` bytes into the input.
+    fn emit<W: Write>(
+        &mut self,
+        out: &mut W,
+        line_num: usize,
+        offset: usize,
+        buf: &[u8],
+    ) -> MyResult<()> {
+        self.emit_part(out, line_num, offset, buf, false)
+    }
+
+    /// Like `emit`, or for the rest of a line when `continued`, which is
+    /// neither numbered nor squeezed.
+    fn emit_part<W: Write>(
+        &mut self,
+        out: &mut W,
+        line_num: usize,
+        offset: usize,
+        buf: &[u8],
+        continued: bool,
+    ) -> MyResult<()> {
+        let config = self.config;
+        self.last_written = Some(line_num);
+        // Only the trailing newline is split off, so a missing final
+        // newline or a `\r` before it is written back untouched.
+        let (line, has_newline) = split_newline(buf);
+        let (line, cr) = config.crlf.split(line, has_newline);
+        let mut line = Cow::Borrowed(line);
+        let blank = line.is_empty() && !continued;
+        if config.squeeze_blank && blank && self.prev_blank {
+            self.squeezed += 1;
+            return Ok(());
+        }
+        if !continued {
+            self.prev_blank = blank;
+        }
+        if config.non_printing || config.show_tabs || config.show_cr {
+            line = Cow::Owned(config.fmt_non_printing(&line));
+        }
+        if config.output == OutputFormat::Jsonl {
+            let numbers = &config.number_format;
+            let number = if continued {
+                None
+            } else if config.number_lines {
+                Some(numbers.number(line_num - 1 - self.squeezed))
+            } else if config.number_nonblank_lines && !blank {
+                self.last_num += 1;
+                Some(numbers.number(self.last_num as usize - 1))
             } else {
-                println!("{}{}", line, if self.non_printing { "$" } else { "" });
+                None
+            };
+            jsonl::write_line(out, self.name, line_num, number, offset, &line)?;
+            if config.follow {
+                out.flush()?;
             }
+            return Ok(());
+        }
+        if let Some(highlighter) = &mut self.highlighter {
+            line = Cow::Owned(highlighter.highlight(&line));
+        }
+        if let Some(pattern) = config.highlight_pattern.as_ref().filter(|_| config.color) {
+            line = Cow::Owned(highlight::highlight_matches(pattern, &line));
+        }
+        if config.number_lines && !continued {
+            line = Cow::Owned(config.fmt_number_lines(&line, line_num - 1 - self.squeezed));
+        } else if config.number_nonblank_lines && !continued {
+            line = Cow::Owned(config.fmt_number_nonblank_lines(&line, &mut self.last_num));
+        }
+        if let Some(stop) = config.expand_tabs {
+            line = Cow::Owned(columns::expand_tabs(&line, stop));
+        }
+        // The `^M` and `$` markers are part of the line as shown, so they
+        // count toward the width it is cut or wrapped to.
+        let show_cr = cr && (config.show_cr || config.non_printing);
+        let show_end = has_newline && config.show_ends;
+        if show_cr || show_end {
+            let line = line.to_mut();
+            if show_cr {
+                line.extend_from_slice(b"^M");
+            }
+            if show_end {
+                line.push(b'$');
+            }
+        }
+        if let Some(width) = config.truncate {
+            line = Cow::Owned(columns::truncate(&line, width));
+        } else if let Some(width) = config.wrap {
+            line = Cow::Owned(columns::wrap(&line, width, &config.continuation_marker()));
         }
+        out.write_all(&line)?;
+        if cr && !show_cr {
+            out.write_all(b"\r")?;
+        }
+        if has_newline {
+            out.write_all(b"\n")?;
+        }
+        if config.follow {
+            out.flush()?;
+        }
+        Ok(())
     }
 }
 
@@ -97,53 +523,1074 @@ pub fn get_args() -> MyResult<Config> {
                 .long("number-nonblank")
                 .takes_value(false),
         )
+        .arg(
+            Arg::with_name("number_format")
+                .help("Justify line numbers left, right, or right with leading zeros")
+                .long("number-format")
+                .takes_value(true)
+                .value_name("format")
+                .possible_values(&["ln", "rn", "rz"]),
+        )
+        .arg(
+            Arg::with_name("number_width")
+                .help("Width of the line number field [default: 6]")
+                .long("number-width")
+                .takes_value(true)
+                .value_name("width"),
+        )
+        .arg(
+            Arg::with_name("number_separator")
+                .help("Text written after each line number [default: TAB]")
+                .long("number-separator")
+                .takes_value(true)
+                .value_name("text")
+                .allow_hyphen_values(true),
+        )
+        .arg(
+            Arg::with_name("starting_line_number")
+                .help("The number given to the first line [default: 1]")
+                .long("starting-line-number")
+                .takes_value(true)
+                .value_name("number"),
+        )
+        .arg(
+            Arg::with_name("line_increment")
+                .help("The step between line numbers [default: 1]")
+                .long("line-increment")
+                .takes_value(true)
+                .value_name("number"),
+        )
         .arg(
             Arg::with_name("line_range")
                 .help("Display only the specified line range")
                 .short("r")
                 .long("line-range")
                 .takes_value(true)
-                .value_name("start:end"),
+                .value_name("start:end")
+                .allow_hyphen_values(true)
+                .long_help(
+                    "Display only the specified line ranges, e.g. 3:10, 5:, :20, -10: or \
+                     1:3,10:12. Negative numbers count from the end of the input.",
+                ),
         )
         .arg(
             Arg::with_name("non_printing")
-                .help("Display non-printing characters")
-                .short("e")
+                .help("Use ^ and M- notation, except for LFD and TAB")
+                .short("v")
                 .long("show-nonprinting")
                 .takes_value(false),
         )
+        .arg(
+            Arg::with_name("show_ends")
+                .help("Display $ at end of each line")
+                .short("E")
+                .long("show-ends")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("show_tabs")
+                .help("Display TAB characters as ^I")
+                .short("T")
+                .long("show-tabs")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("show_all")
+                .help("Equivalent to -vET")
+                .short("A")
+                .long("show-all")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("show_nonprinting_ends")
+                .help("Equivalent to -vE")
+                .short("e")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("show_nonprinting_tabs")
+                .help("Equivalent to -vT")
+                .short("t")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("squeeze_blank")
+                .help("Suppress repeated empty output lines")
+                .short("s")
+                .long("squeeze-blank")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("continuous")
+                .help("Number lines and apply --line-range across all files as one stream")
+                .long("continuous")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("paging")
+                .help("Send output through $PAGER or the built-in pager")
+                .long("paging")
+                .takes_value(true)
+                .value_name("when")
+                .possible_values(&["always", "never", "auto"])
+                .default_value("auto"),
+        )
+        .arg(
+            Arg::with_name("decompress")
+                .help("Decompress gzip, bzip2, xz and zstd input, detected by magic bytes")
+                .short("z")
+                .long("decompress")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("highlight")
+                .help("Highlight syntax, detecting the language from the file extension")
+                .long("highlight")
+                .takes_value(true)
+                .value_name("lang")
+                .min_values(0)
+                .require_equals(true)
+                .possible_values(&["rust", "toml", "json"]),
+        )
+        .arg(
+            Arg::with_name("color")
+                .help("When to use colors")
+                .long("color")
+                .takes_value(true)
+                .value_name("when")
+                .possible_values(&["always", "never", "auto"])
+                .default_value("auto"),
+        )
+        .arg(
+            Arg::with_name("hex")
+                .help("Display input as a hex dump; --line-range then selects byte offsets")
+                .short("x")
+                .long("hex")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("hex_width")
+                .help("Bytes per row in a hex dump [default: 16]")
+                .long("hex-width")
+                .takes_value(true)
+                .value_name("bytes")
+                .requires("hex"),
+        )
+        .arg(
+            Arg::with_name("hex_group")
+                .help("Bytes per group in a hex dump, 0 for none [default: 2]")
+                .long("hex-group")
+                .takes_value(true)
+                .value_name("bytes")
+                .requires("hex"),
+        )
+        .arg(
+            Arg::with_name("follow")
+                .help("Keep reading the last file as it grows, reopening it if rotated")
+                .short("f")
+                .long("follow")
+                .takes_value(false)
+                .conflicts_with_all(&["hex", "continuous", "decompress"]),
+        )
+        .arg(
+            Arg::with_name("head")
+                .help("Display only the first N lines")
+                .long("head")
+                .takes_value(true)
+                .value_name("N")
+                .conflicts_with("hex"),
+        )
+        .arg(
+            Arg::with_name("tail")
+                .help("Display only the last N lines")
+                .long("tail")
+                .takes_value(true)
+                .value_name("N")
+                .conflicts_with("hex"),
+        )
+        .arg(
+            Arg::with_name("match")
+                .help("Display only lines matching a regular expression")
+                .long("match")
+                .visible_alias("grep")
+                .takes_value(true)
+                .value_name("regex"),
+        )
+        .arg(
+            Arg::with_name("invert_match")
+                .help("Display only lines that do not match --match")
+                .long("invert-match")
+                .takes_value(false)
+                .requires("match"),
+        )
+        .arg(
+            Arg::with_name("context")
+                .help("Display N lines of context around each match")
+                .short("C")
+                .long("context")
+                .takes_value(true)
+                .value_name("N")
+                .requires("match"),
+        )
+        .arg(
+            Arg::with_name("before_context")
+                .help("Display N lines of context before each match")
+                .long("before-context")
+                .takes_value(true)
+                .value_name("N")
+                .requires("match"),
+        )
+        .arg(
+            Arg::with_name("after_context")
+                .help("Display N lines of context after each match")
+                .long("after-context")
+                .takes_value(true)
+                .value_name("N")
+                .requires("match"),
+        )
+        .arg(
+            Arg::with_name("highlight_pattern")
+                .help("Color matches of a regular expression without filtering lines")
+                .long("highlight-pattern")
+                .takes_value(true)
+                .value_name("regex")
+                .conflicts_with("highlight"),
+        )
+        .arg(
+            Arg::with_name("recursive")
+                .help("Read all files under directories, in sorted order, skipping ignored files")
+                .short("R")
+                .long("recursive")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("include")
+                .help("Read only files matching a glob when recursing")
+                .long("include")
+                .takes_value(true)
+                .value_name("glob")
+                .multiple(true)
+                .number_of_values(1)
+                .requires("recursive"),
+        )
+        .arg(
+            Arg::with_name("exclude")
+                .help("Skip files matching a glob when recursing")
+                .long("exclude")
+                .takes_value(true)
+                .value_name("glob")
+                .multiple(true)
+                .number_of_values(1)
+                .requires("recursive"),
+        )
+        .arg(
+            Arg::with_name("headers")
+                .help("Print the name of each file before its contents")
+                .long("headers")
+                .takes_value(true)
+                .value_name("format")
+                .min_values(0)
+                .require_equals(true)
+                .possible_values(&["plain", "json"])
+                .conflicts_with("continuous"),
+        )
+        .arg(
+            Arg::with_name("keep_going")
+                .help("Report inputs that cannot be read and carry on (the default)")
+                .long("keep-going")
+                .takes_value(false)
+                .overrides_with("fail_fast"),
+        )
+        .arg(
+            Arg::with_name("fail_fast")
+                .help("Stop at the first input that cannot be read")
+                .long("fail-fast")
+                .takes_value(false)
+                .overrides_with("keep_going"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .help("How to write selected lines [default: text]")
+                .long("output")
+                .takes_value(true)
+                .value_name("format")
+                .possible_values(&["text", "jsonl"])
+                .conflicts_with_all(&["hex", "continuous", "headers"]),
+        )
+        .arg(
+            Arg::with_name("jobs")
+                .help("Read up to N files ahead on worker threads")
+                .short("j")
+                .long("jobs")
+                .takes_value(true)
+                .value_name("N")
+                .conflicts_with("continuous"),
+        )
+        .arg(
+            Arg::with_name("from_encoding")
+                .help("Encoding of input without a byte order mark, such as utf-16le or latin1")
+                .long("from-encoding")
+                .takes_value(true)
+                .value_name("encoding"),
+        )
+        .arg(
+            Arg::with_name("to_encoding")
+                .help("Encoding to write the output in [default: utf-8]")
+                .long("to-encoding")
+                .takes_value(true)
+                .value_name("encoding"),
+        )
+        .arg(
+            Arg::with_name("strip_bom")
+                .help("Remove any byte order mark, converting UTF-16 input to UTF-8")
+                .long("strip-bom")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("crlf")
+                .help("Keep line endings, strip CR from CRLF, or add CR to every line")
+                .long("crlf")
+                .takes_value(true)
+                .value_name("mode")
+                .possible_values(&["keep", "strip", "add"])
+                .default_value("keep"),
+        )
+        .arg(
+            Arg::with_name("show_cr")
+                .help("Display carriage returns as ^M")
+                .long("show-cr")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("expand_tabs")
+                .help("Replace tabs with spaces up to every Nth column [default: 8]")
+                .long("expand-tabs")
+                .takes_value(true)
+                .value_name("N")
+                .min_values(0)
+                .require_equals(true)
+                .conflicts_with("hex"),
+        )
+        .arg(
+            Arg::with_name("wrap")
+                .help("Wrap lines wider than the terminal or the given number of columns")
+                .long("wrap")
+                .takes_value(true)
+                .value_name("columns")
+                .min_values(0)
+                .require_equals(true)
+                .conflicts_with_all(&["hex", "truncate"]),
+        )
+        .arg(
+            Arg::with_name("truncate")
+                .help("Cut lines wider than the terminal or the given number of columns")
+                .long("truncate")
+                .takes_value(true)
+                .value_name("columns")
+                .min_values(0)
+                .require_equals(true)
+                .conflicts_with("hex"),
+        )
         .get_matches();
 
-    Ok(Config::new(
-        matches.values_of_lossy("files").unwrap(),
-        matches.is_present("number_lines"),
-        matches.is_present("number_nonblank_lines"),
-        matches.value_of("line_range").map(|range| {
-            let mut split = range.split(':');
-            let start = split.next().unwrap().parse::<usize>().unwrap();
-            let end = split.next().unwrap().parse::<usize>().unwrap();
-            (start, end)
-        }),
-        matches.is_present("non_printing"),
-    ))
+    let show_all = matches.is_present("show_all");
+    let show_nonprinting_ends = matches.is_present("show_nonprinting_ends");
+    let show_nonprinting_tabs = matches.is_present("show_nonprinting_tabs");
+
+    let mut line_range = matches
+        .value_of("line_range")
+        .map(LineRanges::parse)
+        .transpose()?;
+    for (arg, select) in [
+        ("head", LineRanges::head as fn(usize) -> LineRanges),
+        ("tail", LineRanges::tail),
+    ] {
+        if let Some(lines) = matches.value_of(arg) {
+            let ranges = select(parse_count(lines, &format!("--{} line count", arg))?);
+            match &mut line_range {
+                Some(line_range) => line_range.extend(ranges),
+                None => line_range = Some(ranges),
+            }
+        }
+    }
+    if matches.is_present("follow") && line_range.as_ref().is_some_and(LineRanges::needs_total) {
+        return Err("--follow cannot be used with line ranges counted from the end".into());
+    }
+
+    let regex = |arg: &str| -> MyResult<Option<Regex>> {
+        matches
+            .value_of(arg)
+            .map(|pattern| {
+                Regex::new(pattern).map_err(|err| {
+                    format!("Invalid --{} pattern: {}", arg.replace('_', "-"), err).into()
+                })
+            })
+            .transpose()
+    };
+    let pattern = regex("match")?;
+    let context = |arg: &str| -> MyResult<Option<usize>> {
+        matches
+            .value_of(arg)
+            .map(|lines| parse_count(lines, "context line count"))
+            .transpose()
+    };
+    let both_context = context("context")?;
+    let before_context = context("before_context")?.or(both_context).unwrap_or(0);
+    let after_context = context("after_context")?.or(both_context).unwrap_or(0);
+
+    // clap 2 cannot require one of two conflicting flags, so check here.
+    let numbering =
+        matches.is_present("number_lines") || matches.is_present("number_nonblank_lines");
+    for arg in [
+        "number_format",
+        "number_width",
+        "number_separator",
+        "starting_line_number",
+        "line_increment",
+    ] {
+        if matches.is_present(arg) && !numbering {
+            let arg = arg.replace('_', "-");
+            return Err(format!("--{} requires --number or --number-nonblank", arg).into());
+        }
+    }
+    let number_format = {
+        let default = NumberFormat::default();
+        let width = match matches.value_of("number_width") {
+            Some(width) => parse_count(width, "line number width")?,
+            None => default.width,
+        };
+        if width == 0 {
+            return Err("Invalid line number width \"0\"".into());
+        }
+        if width > number::MAX_WIDTH {
+            return Err(format!(
+                "Invalid line number width \"{}\": must be at most {}",
+                width,
+                number::MAX_WIDTH
+            )
+            .into());
+        }
+        NumberFormat {
+            width,
+            justify: match matches.value_of("number_format") {
+                Some(justify) => justify.parse()?,
+                None => default.justify,
+            },
+            separator: match matches.value_of("number_separator") {
+                Some(separator) => separator.to_string(),
+                None => default.separator,
+            },
+            start: match matches.value_of("starting_line_number") {
+                Some(start) => parse_count(start, "starting line number")?,
+                None => default.start,
+            },
+            increment: match matches.value_of("line_increment") {
+                Some(increment) => parse_count(increment, "line increment")?,
+                None => default.increment,
+            },
+        }
+    };
+
+    let jobs = match matches.value_of("jobs") {
+        Some(jobs) => parse_count(jobs, "job count")?,
+        None => 1,
+    };
+    if jobs == 0 {
+        return Err("Invalid job count \"0\"".into());
+    }
+    if jobs > prefetch::MAX_JOBS {
+        return Err(format!(
+            "Invalid job count \"{}\": must be at most {}",
+            jobs,
+            prefetch::MAX_JOBS
+        )
+        .into());
+    }
+
+    let columns = |arg: &str, what: &str, default: fn() -> usize| -> MyResult<Option<usize>> {
+        if !matches.is_present(arg) {
+            return Ok(None);
+        }
+        let columns = match matches.value_of(arg) {
+            Some(columns) => parse_count(columns, what)?,
+            None => default(),
+        };
+        if columns == 0 {
+            return Err(format!("Invalid {} \"0\"", what).into());
+        }
+        Ok(Some(columns))
+    };
+    let terminal_width = || pager::terminal_size().map_or(80, |(_, cols)| cols);
+    let expand_tabs = columns("expand_tabs", "tab stop", || 8)?;
+    let wrap = columns("wrap", "wrap width", terminal_width)?;
+    let truncate = columns("truncate", "truncate width", terminal_width)?;
+    // Numbered lines need room past the number, or it would be cut itself.
+    if numbering {
+        let format = &number_format;
+        let prefix = format!("{:width$}{}", "", format.separator, width = format.width);
+        let prefix = columns::width(prefix.as_bytes());
+        for (columns, what) in [(wrap, "wrap width"), (truncate, "truncate width")] {
+            if let Some(columns) = columns.filter(|&columns| columns <= prefix) {
+                return Err(format!(
+                    "Invalid {} \"{}\": line numbers take {} columns",
+                    what, columns, prefix
+                )
+                .into());
+            }
+        }
+    }
+
+    let hex = if matches.is_present("hex") {
+        let default = HexFormat::default();
+        let width = match matches.value_of("hex_width") {
+            Some(width) => parse_count(width, "hex width")?,
+            None => default.width,
+        };
+        if width == 0 {
+            return Err("Invalid hex width \"0\"".into());
+        }
+        if width > hex::MAX_WIDTH {
+            return Err(format!(
+                "Invalid hex width \"{}\": must be at most {}",
+                width,
+                hex::MAX_WIDTH
+            )
+            .into());
+        }
+        let group = match matches.value_of("hex_group") {
+            Some(group) => parse_count(group, "hex group")?,
+            None => default.group,
+        };
+        Some(HexFormat { width, group })
+    } else {
+        None
+    };
+
+    Ok(Config {
+        number_lines: matches.is_present("number_lines"),
+        number_nonblank_lines: matches.is_present("number_nonblank_lines"),
+        number_format,
+        line_range,
+        non_printing: matches.is_present("non_printing")
+            || show_all
+            || show_nonprinting_ends
+            || show_nonprinting_tabs,
+        show_ends: matches.is_present("show_ends") || show_all || show_nonprinting_ends,
+        show_tabs: matches.is_present("show_tabs") || show_all || show_nonprinting_tabs,
+        squeeze_blank: matches.is_present("squeeze_blank"),
+        continuous: matches.is_present("continuous"),
+        paging: matches.value_of("paging").unwrap().parse()?,
+        decompress: matches.is_present("decompress"),
+        highlight: matches.is_present("highlight"),
+        language: matches.value_of("highlight").map(str::parse).transpose()?,
+        hex,
+        follow: matches.is_present("follow"),
+        pattern,
+        highlight_pattern: regex("highlight_pattern")?,
+        invert_match: matches.is_present("invert_match"),
+        recursive: matches.is_present("recursive"),
+        include: matches.values_of_lossy("include").unwrap_or_default(),
+        exclude: matches.values_of_lossy("exclude").unwrap_or_default(),
+        headers: match matches.value_of("headers") {
+            Some(format) => Some(format.parse()?),
+            None if matches.is_present("headers") => Some(Headers::default()),
+            None => None,
+        },
+        fail_fast: matches.is_present("fail_fast"),
+        jobs,
+        from_encoding: matches
+            .value_of("from_encoding")
+            .map(encoding::parse)
+            .transpose()?,
+        to_encoding: matches
+            .value_of("to_encoding")
+            .map(encoding::parse)
+            .transpose()?,
+        strip_bom: matches.is_present("strip_bom"),
+        crlf: matches.value_of("crlf").unwrap().parse()?,
+        show_cr: matches.is_present("show_cr"),
+        expand_tabs,
+        wrap,
+        truncate,
+        output: matches
+            .value_of("output")
+            .map(str::parse)
+            .transpose()?
+            .unwrap_or_default(),
+        before_context,
+        after_context,
+        color: match matches.value_of("color") {
+            Some("always") => true,
+            Some("never") => false,
+            _ => io::stdout().is_terminal(),
+        },
+        ..Config::new(matches.values_of_lossy("files").unwrap())
+    })
+}
+
+fn parse_count(value: &str, what: &str) -> MyResult<usize> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid {} \"{}\"", what, value).into())
 }
 
-fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
-    match filename {
-        "-" => Ok(Box::new(BufReader::new(io::stdin()))),
-        _ => Ok(Box::new(BufReader::new(File::open(filename)?))),
+/// Opens a regular file positioned at the start of its last `lines` lines,
+/// found by reading backwards from the end so that `--tail` does not scan
+/// the whole file. Returns `None` for anything that cannot seek.
+fn open_tail(filename: &str, lines: usize) -> io::Result<Option<Box<dyn BufRead>>> {
+    if filename == "-" {
+        return Ok(None);
     }
+    let mut file = File::open(filename)?;
+    let meta = file.metadata()?;
+    if !meta.is_file() {
+        return Ok(None);
+    }
+    let len = meta.len();
+    let mut buf = vec![0; 64 * 1024];
+    let mut start = len;
+    let mut found = 0;
+    let mut pos = len;
+    'scan: while pos > 0 && found < lines {
+        let chunk_start = pos.saturating_sub(buf.len() as u64);
+        let chunk = &mut buf[..(pos - chunk_start) as usize];
+        file.seek(SeekFrom::Start(chunk_start))?;
+        file.read_exact(chunk)?;
+        for (i, &byte) in chunk.iter().enumerate().rev() {
+            let at = chunk_start + i as u64;
+            // The newline ending the last line does not start another one.
+            if byte == b'\n' && at + 1 != len {
+                found += 1;
+                start = at + 1;
+                if found == lines {
+                    break 'scan;
+                }
+            }
+        }
+        pos = chunk_start;
+    }
+    if found < lines {
+        start = 0;
+    }
+    file.seek(SeekFrom::Start(start))?;
+    Ok(Some(Box::new(BufReader::new(file))))
 }
 
-// TODO: Consider using a function to manage the output based on provided flags
-// TODO: See about adding paging support
-// TODO: Add support for displaying non-printing characters
-pub fn run(config: Config) -> MyResult<()> {
-    for filename in &config.files {
-        match open(&filename) {
-            Err(err) => eprintln!("Failed to open {}: {}", filename, err),
-            Ok(file) => config.process_flags(file),
+/// Implements `Read::read` for a type that reads through its own
+/// `BufRead` buffer, copying out as much of it as fits in `buf`.
+fn read_buffered<R: BufRead>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let available = reader.fill_buf()?;
+    let len = available.len().min(buf.len());
+    buf[..len].copy_from_slice(&available[..len]);
+    reader.consume(len);
+    Ok(len)
+}
+
+/// Opens a file for reading, refusing directories up front rather than
+/// failing on the first read.
+fn open_file(filename: &str) -> io::Result<File> {
+    let file = File::open(filename)?;
+    if file.metadata()?.is_dir() {
+        return Err(io::Error::other("Is a directory"));
+    }
+    Ok(file)
+}
+
+/// How `open` prepares an input: decompressed, then converted to UTF-8.
+#[derive(Debug, Clone, Copy, Default)]
+struct OpenOptions {
+    decompress: bool,
+    transcode: bool,
+    /// The encoding to assume when there is no byte order mark.
+    encoding: Option<&'static Encoding>,
+}
+
+impl OpenOptions {
+    fn transcode(&self, file: Box<dyn BufRead>) -> Box<dyn BufRead> {
+        if self.transcode {
+            encoding::decoder(file, self.encoding)
+        } else {
+            file
         }
     }
+}
+
+fn open(filename: &str, options: OpenOptions) -> io::Result<Box<dyn BufRead>> {
+    let mut file: Box<dyn BufRead> = match filename {
+        "-" => Box::new(BufReader::new(io::stdin())),
+        _ => Box::new(BufReader::new(open_file(filename)?)),
+    };
+    if options.decompress {
+        file = decompress::decoder(file)?;
+    }
+    Ok(options.transcode(file))
+}
+
+/// An opened input, either copied through byte for byte or read line by
+/// line to be formatted.
+enum Input {
+    Stdin,
+    File(File),
+    Lines(Box<dyn BufRead>),
+    /// An input being read ahead by `--jobs`.
+    Fetched(prefetch::Fetched),
+}
+
+fn open_input(config: &Config, plain: bool, follow: bool, filename: &str) -> io::Result<Input> {
+    if plain {
+        return match filename {
+            "-" => Ok(Input::Stdin),
+            _ => open_file(filename).map(Input::File),
+        };
+    }
+    if follow && filename != "-" {
+        let options = config.open_options();
+        let file = follow::Follow::open(filename, !options.transcode)?;
+        return Ok(Input::Lines(options.transcode(Box::new(file))));
+    }
+    open_from(filename, config.open_options(), config.seekable_tail()).map(Input::Lines)
+}
+
+/// Opens a file with `open`, or at its last `tail` lines where it can seek.
+fn open_from(
+    filename: &str,
+    options: OpenOptions,
+    tail: Option<usize>,
+) -> io::Result<Box<dyn BufRead>> {
+    if let Some(lines) = tail {
+        if let Some(file) = open_tail(filename, lines)? {
+            return Ok(file);
+        }
+    }
+    open(filename, options)
+}
+
+/// Copies an input through untouched, in large chunks written straight
+/// from the read buffer. From a file to stdout, `io::copy` instead moves the
+/// bytes inside the kernel.
+fn copy<R: Read, W: Write>(input: R, out: &mut W) -> MyResult<()> {
+    // Anything already buffered, such as a header, goes out first.
+    out.flush()?;
+    io::copy(&mut BufReader::with_capacity(128 * 1024, input), out)?;
     Ok(())
 }
+
+/// Like `copy`, for an input that already reads in large chunks.
+fn copy_buf<R: BufRead, W: Write>(mut input: R, out: &mut W) -> MyResult<()> {
+    loop {
+        let chunk = input.fill_buf()?;
+        if chunk.is_empty() {
+            return Ok(());
+        }
+        out.write_all(chunk)?;
+        let len = chunk.len();
+        input.consume(len);
+    }
+}
+
+/// Passes reads through, noting in `failed` when one goes wrong, so that an
+/// error can be put down to the input rather than to writing the output.
+struct Reading<R> {
+    inner: R,
+    failed: Rc<Cell<bool>>,
+}
+
+impl<R> Reading<R> {
+    fn new(inner: R, failed: &Rc<Cell<bool>>) -> Self {
+        Reading {
+            inner,
+            failed: Rc::clone(failed),
+        }
+    }
+
+    fn note<T>(failed: &Cell<bool>, result: io::Result<T>) -> io::Result<T> {
+        if result
+            .as_ref()
+            .is_err_and(|err| err.kind() != io::ErrorKind::Interrupted)
+        {
+            failed.set(true);
+        }
+        result
+    }
+}
+
+impl<R: Read> Read for Reading<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        Self::note(&self.failed, self.inner.read(buf))
+    }
+}
+
+impl<R: BufRead> BufRead for Reading<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        Self::note(&self.failed, self.inner.fill_buf())
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt);
+    }
+}
+
+pub fn run(config: Config) -> MyResult<()> {
+    let paging = match config.paging {
+        _ if config.follow => Paging::Never,
+        // Input typed at the terminal is echoed back as it is read, rather
+        // than handed to a pager reading keys from the same terminal.
+        Paging::Auto if config.files.iter().any(|f| f == "-") && io::stdin().is_terminal() => {
+            Paging::Never
+        }
+        paging => paging,
+    };
+    let mut out = pager::Output::new(paging);
+    let result = match &mut out {
+        // Handing `io::copy` stdout itself rather than the pager wrapper
+        // lets it fill a file with `copy_file_range` where the OS has it.
+        pager::Output::Stdout(stdout) if config.is_plain() && pager::stdout_is_file() => {
+            run_to(config, stdout.get_mut())
+        }
+        _ => run_to(config, &mut out),
+    };
+    out.finish().and(result)
+}
+
+/// Like `run`, but writes to `writer` instead of stdout and never pages.
+pub fn run_to<W: Write>(config: Config, writer: W) -> MyResult<()> {
+    match config.to_encoding.filter(|&encoding| encoding != UTF_8) {
+        Some(encoding) => {
+            let mut writer = encoding::Encode::new(writer, encoding);
+            let result = write_inputs(config, &mut writer);
+            match writer.finish() {
+                Err(err) if err.kind() != io::ErrorKind::BrokenPipe => Err(err.into()),
+                _ => result,
+            }
+        }
+        None => write_inputs(config, writer),
+    }
+}
+
+fn write_inputs<W: Write>(mut config: Config, mut writer: W) -> MyResult<()> {
+    let mut failed = 0;
+    if config.recursive {
+        config.files = walk::expand(&config.files, &config.include, &config.exclude, &mut failed)?;
+    }
+    if config.fail_fast && failed > 0 {
+        return Err(CatrError::Failed(failed));
+    }
+    let plain = config.is_plain();
+    if config.continuous && !plain {
+        let stream_failed = Rc::new(Cell::new(0));
+        let stream = concat::Concat::new(
+            config.files.clone(),
+            config.open_options(),
+            config.fail_fast,
+            Rc::clone(&stream_failed),
+        );
+        let result = config.process_flags_to(Box::new(stream), &mut writer);
+        failed += stream_failed.get();
+        match result {
+            Err(err) if !err.is_broken_pipe() => return Err(err),
+            _ => {}
+        }
+    } else {
+        // Only the last input is followed, as anything after it would never
+        // be reached. Neither it nor stdin is read ahead.
+        let followed = |i: usize| config.follow && i + 1 == config.files.len();
+        let prefetched = |i: usize, filename: &str| filename != "-" && !followed(i);
+        // A plain copy into a file already happens inside the kernel, which
+        // reading ahead through memory would only slow down.
+        let read_ahead = config.jobs > 1 && !(plain && pager::stdout_is_file());
+        let prefetch = read_ahead.then(|| {
+            let files = (config.files.iter().enumerate())
+                .filter(|(i, filename)| prefetched(*i, filename))
+                .map(|(_, filename)| filename.clone())
+                .collect();
+            let (options, tail) = (config.open_options(), config.seekable_tail());
+            prefetch::Prefetch::new(files, config.jobs, move |filename: &str| {
+                open_from(filename, options, tail)
+            })
+        });
+        let mut prefetch = prefetch.transpose()?;
+        // A plain copy from a file into a file happens inside the kernel,
+        // where an error cannot be told to come from either side. Wrapping
+        // the input would stop that, so such errors count as writing ones.
+        let in_kernel = plain && pager::stdout_is_file();
+        let mut read = 0;
+        for (i, filename) in config.files.iter().enumerate() {
+            let read_failed = Rc::new(Cell::new(false));
+            let opened = match &mut prefetch {
+                Some(prefetch) if prefetched(i, filename) => match prefetch.next() {
+                    Some(fetched) => fetched.map(Input::Fetched),
+                    None => Err(CatrError::Io(io::Error::other("read-ahead stopped"))),
+                },
+                _ => open_input(&config, plain, followed(i), filename).map_err(|source| {
+                    CatrError::Open {
+                        path: filename.clone(),
+                        source,
+                    }
+                }),
+            };
+            let result = opened.and_then(|input| {
+                let result = match config.headers {
+                    Some(headers) => headers.write(&mut writer, filename, read, config.color),
+                    None => Ok(()),
+                }
+                .and_then(|_| match input {
+                    Input::Stdin => {
+                        copy(Reading::new(io::stdin().lock(), &read_failed), &mut writer)
+                    }
+                    Input::File(file) if in_kernel => copy(file, &mut writer),
+                    Input::File(file) => copy(Reading::new(file, &read_failed), &mut writer),
+                    Input::Fetched(file) if plain => {
+                        copy_buf(Reading::new(file, &read_failed), &mut writer)
+                    }
+                    Input::Fetched(file) => {
+                        let file = Box::new(Reading::new(file, &read_failed));
+                        config.process_input(file, Some(filename), &mut writer)
+                    }
+                    Input::Lines(file) => {
+                        let file = Box::new(Reading::new(file, &read_failed));
+                        config.process_input(file, Some(filename), &mut writer)
+                    }
+                })
+                .map_err(|err| match read_failed.get() {
+                    true => CatrError::read(filename, err),
+                    false => err,
+                });
+                read += 1;
+                result
+            });
+            if let Err(err) = result {
+                if err.is_broken_pipe() {
+                    break;
+                }
+                // An input that fails is skipped, but once the output fails
+                // there is nowhere left to write the rest.
+                if let CatrError::Io(_) = err {
+                    return Err(err);
+                }
+                eprintln!("{}", err);
+                failed += 1;
+                if config.fail_fast {
+                    break;
+                }
+            }
+        }
+    }
+    match writer.flush() {
+        Err(err) if err.kind() != io::ErrorKind::BrokenPipe => Err(err.into()),
+        _ if failed > 0 => Err(CatrError::Failed(failed)),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn render(config: &Config, input: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        config
+            .process_flags_to(Box::new(Cursor::new(input.to_vec())), &mut out)
+            .unwrap();
+        out
+    }
+
+    fn config() -> Config {
+        Config::new(vec![])
+    }
+
+    #[test]
+    fn plain_is_byte_identical() {
+        let input = b"caf\xe9\r\n\n\xff last";
+        assert_eq!(render(&config(), input), input);
+    }
+
+    #[test]
+    fn number_lines() {
+        let config = Config {
+            number_lines: true,
+            ..config()
+        };
+        assert_eq!(
+            render(&config, b"a\n\nb"),
+            b"     1\ta\n     2\t\n     3\tb"
+        );
+    }
+
+    #[test]
+    fn number_nonblank_lines() {
+        let config = Config {
+            number_nonblank_lines: true,
+            ..config()
+        };
+        assert_eq!(render(&config, b"a\n\nb\n"), b"     1\ta\n\n     2\tb\n");
+    }
+
+    #[test]
+    fn show_all() {
+        let config = Config {
+            non_printing: true,
+            show_ends: true,
+            show_tabs: true,
+            ..config()
+        };
+        assert_eq!(
+            render(&config, b"\t\x01\x7f\x89\xe9\xff\r\n"),
+            b"^I^A^?M-^IM-iM-^?^M$\n"
+        );
+    }
+
+    #[test]
+    fn squeeze_blank_numbers_output_lines() {
+        let config = Config {
+            squeeze_blank: true,
+            number_lines: true,
+            ..config()
+        };
+        assert_eq!(
+            render(&config, b"a\n\n\n\nb\n"),
+            b"     1\ta\n     2\t\n     3\tb\n"
+        );
+    }
+
+    #[test]
+    fn line_ranges() {
+        let config = Config {
+            line_range: Some(LineRanges::parse("1,-2:").unwrap()),
+            ..config()
+        };
+        assert_eq!(render(&config, b"1\n2\n3\n4\n5\n"), b"1\n4\n5\n");
+    }
+
+    #[test]
+    fn line_ranges_held_back_from_end() {
+        let config = Config {
+            line_range: Some(LineRanges::parse("-4:-3,2:3").unwrap()),
+            number_lines: true,
+            ..config()
+        };
+        assert_eq!(
+            render(&config, b"1\n2\n3\n4\n5\n6\n"),
+            b"     2\t2\n     3\t3\n     4\t4\n"
+        );
+    }
+
+    #[test]
+    fn run_to_writer() {
+        let config = Config {
+            number_lines: true,
+            continuous: true,
+            ..Config::new(vec![
+                "tests/inputs/fox.txt".to_string(),
+                "tests/inputs/spiders.txt".to_string(),
+                "tests/inputs/the-bustle.txt".to_string(),
+            ])
+        };
+        let mut out = Vec::new();
+        run_to(config, &mut out).unwrap();
+        assert_eq!(
+            out,
+            std::fs::read("tests/expected/all.continuous.n.out").unwrap()
+        );
+    }
+}
@@ -1,6 +1,13 @@
+use catr::CatrError;
+
 fn main() {
-    if let Err(e) = catr::get_args().and_then(catr::run) {
-        eprintln!("{}", e);
-        std::process::exit(1);
+    match catr::get_args().and_then(catr::run) {
+        Ok(()) => {}
+        // Each failed input has already been reported.
+        Err(CatrError::Failed(_)) => std::process::exit(1),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
     }
 }
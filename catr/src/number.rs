@@ -0,0 +1,75 @@
+use std::str::FromStr;
+
+/// Where a line number sits in its field, as with `nl -n`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Justify {
+    /// Left justified, `ln`.
+    Left,
+    /// Right justified, `rn`.
+    #[default]
+    Right,
+    /// Right justified with leading zeros, `rz`.
+    RightZero,
+}
+
+impl FromStr for Justify {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "ln" => Ok(Justify::Left),
+            "rn" => Ok(Justify::Right),
+            "rz" => Ok(Justify::RightZero),
+            _ => Err(format!("Invalid number format \"{}\"", value)),
+        }
+    }
+}
+
+/// The widest line number field `--number-width` accepts.
+pub const MAX_WIDTH: usize = 1024;
+
+/// How `-n` and `-b` format line numbers: the field width, justification,
+/// the separator written after the number, and the first number and step
+/// between numbers, as with `nl -w`, `-n`, `-s`, `-v` and `-i`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NumberFormat {
+    pub width: usize,
+    pub justify: Justify,
+    pub separator: String,
+    pub start: usize,
+    pub increment: usize,
+}
+
+impl Default for NumberFormat {
+    fn default() -> Self {
+        NumberFormat {
+            width: 6,
+            justify: Justify::Right,
+            separator: "\t".to_string(),
+            start: 1,
+            increment: 1,
+        }
+    }
+}
+
+impl NumberFormat {
+    /// The number given to the line at 0-based `index` among those numbered,
+    /// which stops growing at `usize::MAX` rather than overflowing.
+    pub fn number(&self, index: usize) -> usize {
+        self.start
+            .saturating_add(index.saturating_mul(self.increment))
+    }
+
+    /// The number for the line at `index`, padded and followed by the
+    /// separator.
+    pub fn format(&self, index: usize) -> String {
+        let number = self.number(index);
+        let width = self.width;
+        let separator = &self.separator;
+        match self.justify {
+            Justify::Left => format!("{:<width$}{}", number, separator),
+            Justify::Right => format!("{:>width$}{}", number, separator),
+            Justify::RightZero => format!("{:0width$}{}", number, separator),
+        }
+    }
+}
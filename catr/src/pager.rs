@@ -0,0 +1,310 @@
+use crate::MyResult;
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, IsTerminal, Read, StdoutLock, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::str::FromStr;
+
+/// When output should go through a pager, selected with `--paging`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Paging {
+    Always,
+    Never,
+    #[default]
+    Auto,
+}
+
+impl FromStr for Paging {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "always" => Ok(Paging::Always),
+            "never" => Ok(Paging::Never),
+            "auto" => Ok(Paging::Auto),
+            _ => Err(format!("Invalid paging mode \"{}\"", value)),
+        }
+    }
+}
+
+/// The destination for everything `run` prints: stdout directly, the
+/// program named by `$PAGER` or `less`, or the internal pager. Stdout is
+/// locked once for the whole run and both it and the pager's stdin are
+/// buffered.
+pub enum Output {
+    Stdout(BufWriter<StdoutLock<'static>>),
+    External(Child, BufWriter<ChildStdin>),
+    Internal(Pager),
+}
+
+impl Output {
+    pub fn new(paging: Paging) -> Self {
+        let page = match paging {
+            Paging::Always => true,
+            Paging::Never => false,
+            Paging::Auto => io::stdout().is_terminal(),
+        };
+        if !page {
+            return Output::Stdout(BufWriter::new(io::stdout().lock()));
+        }
+        match spawn_pager() {
+            Some(mut child) => match child.stdin.take() {
+                Some(stdin) => Output::External(child, BufWriter::new(stdin)),
+                None => Output::Internal(Pager::new()),
+            },
+            None => Output::Internal(Pager::new()),
+        }
+    }
+
+    /// Flushes any buffered output and waits for an external pager to exit.
+    /// A reader that has already gone away, such as `head`, is not an error.
+    pub fn finish(self) -> MyResult<()> {
+        let result = match self {
+            Output::Stdout(mut stdout) => stdout.flush(),
+            Output::External(mut child, mut stdin) => {
+                let flushed = stdin.flush();
+                drop(stdin);
+                child.wait().and(flushed)
+            }
+            Output::Internal(mut pager) => pager.flush(),
+        };
+        match result {
+            Err(err) if err.kind() != io::ErrorKind::BrokenPipe => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Output::Stdout(stdout) => stdout.write(buf),
+            Output::External(_, stdin) => stdin.write(buf),
+            Output::Internal(pager) => pager.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::Stdout(stdout) => stdout.flush(),
+            Output::External(_, stdin) => stdin.flush(),
+            Output::Internal(pager) => pager.flush(),
+        }
+    }
+}
+
+/// Starts `$PAGER`, or `less` when that is unset or cannot be started.
+fn spawn_pager() -> Option<Child> {
+    let spawn = |command: &mut Command| command.stdin(Stdio::piped()).spawn().ok();
+    if let Ok(pager) = env::var("PAGER") {
+        let mut words = pager.split_whitespace();
+        if let Some(child) = words
+            .next()
+            .and_then(|program| spawn(Command::new(program).args(words)))
+        {
+            return Some(child);
+        }
+    }
+    // As git does, let `less` keep colors and exit when everything fits on
+    // one screen, unless `$LESS` says otherwise.
+    let mut less = Command::new("less");
+    if env::var_os("LESS").is_none() {
+        less.env("LESS", "FRX");
+    }
+    spawn(&mut less)
+}
+
+fn stty(tty: &File, args: &[&str]) -> Option<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(tty.try_clone().ok()?)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout)
+        .ok()
+        .map(|s| s.trim().to_string())
+}
+
+/// Returns the size of the controlling terminal as `(rows, columns)`.
+pub fn terminal_size() -> Option<(usize, usize)> {
+    let tty = File::open("/dev/tty").ok()?;
+    let size = stty(&tty, &["size"])?;
+    let mut dims = size.split_whitespace().map(|n| n.parse::<usize>().ok());
+    match (dims.next()??, dims.next()??) {
+        (0, _) | (_, 0) => None,
+        dims => Some(dims),
+    }
+}
+
+/// Whether stdout is a regular file rather than a terminal or pipe.
+#[cfg(unix)]
+pub fn stdout_is_file() -> bool {
+    use std::os::fd::AsFd;
+    io::stdout()
+        .as_fd()
+        .try_clone_to_owned()
+        .map(File::from)
+        .and_then(|stdout| stdout.metadata())
+        .is_ok_and(|meta| meta.is_file())
+}
+
+#[cfg(not(unix))]
+pub fn stdout_is_file() -> bool {
+    false
+}
+
+fn matches(line: &[u8], pattern: &[u8]) -> bool {
+    pattern.is_empty() || line.windows(pattern.len()).any(|w| w == pattern)
+}
+
+/// Switches the terminal to reading single keys without echo, and back to
+/// how it was when dropped, even if reading the key fails. Ctrl-C arrives
+/// as a key rather than a signal, so the terminal is never left this way.
+struct RawMode<'a> {
+    tty: &'a File,
+    saved: Option<String>,
+}
+
+impl<'a> RawMode<'a> {
+    fn new(tty: &'a File) -> Self {
+        let saved = stty(tty, &["-g"]);
+        stty(tty, &["-icanon", "-echo", "-isig", "min", "1"]);
+        RawMode { tty, saved }
+    }
+}
+
+impl Drop for RawMode<'_> {
+    fn drop(&mut self) {
+        if let Some(saved) = &self.saved {
+            stty(self.tty, &[saved]);
+        }
+    }
+}
+
+/// A minimal `more`-style pager used when neither `$PAGER` nor `less` can be
+/// started. Output is shown as it is written, stopping after each screenful
+/// until a key is pressed: Space shows another page, Enter one more line,
+/// `/` skips ahead to a line containing a pattern, `n` to the next one, and
+/// `q` quits. When stdout is not a terminal, output passes straight through.
+pub struct Pager {
+    stdout: StdoutLock<'static>,
+    tty: Option<File>,
+    /// Lines on a screen, less one for the prompt.
+    height: usize,
+    /// Lines left to show before prompting again.
+    left: usize,
+    pattern: Vec<u8>,
+    /// Whether lines are being skipped until one matches `pattern`.
+    searching: bool,
+    /// The part of a skipped line read so far.
+    line: Vec<u8>,
+    quit: bool,
+}
+
+impl Pager {
+    fn new() -> Self {
+        let stdout = io::stdout().lock();
+        let tty = (stdout.is_terminal())
+            .then(|| File::open("/dev/tty").ok())
+            .flatten();
+        let height = terminal_size().map_or(24, |(rows, _)| rows).max(2) - 1;
+        Pager {
+            stdout,
+            tty,
+            height,
+            left: height,
+            pattern: Vec::new(),
+            searching: false,
+            line: Vec::new(),
+            quit: false,
+        }
+    }
+
+    /// Waits for a key at the bottom of the screen and acts on it.
+    fn prompt(&mut self, tty: &File) -> io::Result<()> {
+        write!(self.stdout, "\x1b[7m--More--\x1b[0m")?;
+        self.stdout.flush()?;
+        let mut key = [0];
+        let read = {
+            let _raw = RawMode::new(tty);
+            (&*tty).read(&mut key)?
+        };
+        write!(self.stdout, "\r\x1b[K")?;
+        match (read, key[0]) {
+            (0, _) | (_, b'q' | b'Q' | 0x03) => self.quit = true,
+            (_, b' ' | b'f') => self.left = self.height,
+            (_, b'\n' | b'\r' | b'j') => self.left = 1,
+            (_, b'/') => {
+                write!(self.stdout, "/")?;
+                self.stdout.flush()?;
+                let mut pattern = Vec::new();
+                BufReader::new(tty).read_until(b'\n', &mut pattern)?;
+                if pattern.ends_with(b"\n") {
+                    pattern.pop();
+                }
+                // An empty pattern repeats the last search, as in `more`.
+                if !pattern.is_empty() {
+                    self.pattern = pattern;
+                }
+                self.searching = true;
+            }
+            (_, b'n') => self.searching = true,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn write_lines(&mut self, tty: &File, mut buf: &[u8]) -> io::Result<()> {
+        while !buf.is_empty() {
+            if self.quit {
+                return Err(io::ErrorKind::BrokenPipe.into());
+            }
+            if self.left == 0 && !self.searching {
+                self.prompt(tty)?;
+                continue;
+            }
+            let end = buf
+                .iter()
+                .position(|&b| b == b'\n')
+                .map_or(buf.len(), |i| i + 1);
+            let (line, rest) = buf.split_at(end);
+            buf = rest;
+            let ended = line.ends_with(b"\n");
+            if !self.searching {
+                self.stdout.write_all(line)?;
+                if ended {
+                    self.left -= 1;
+                }
+                continue;
+            }
+            self.line.extend_from_slice(line);
+            if ended {
+                if matches(&self.line, &self.pattern) {
+                    self.searching = false;
+                    self.stdout.write_all(&self.line)?;
+                    self.left = self.height - 1;
+                }
+                self.line.clear();
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Write for Pager {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let Some(tty) = self.tty.take() else {
+            return self.stdout.write(buf);
+        };
+        let result = self.write_lines(&tty, buf);
+        self.tty = Some(tty);
+        result.map(|_| buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stdout.flush()
+    }
+}
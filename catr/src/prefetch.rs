@@ -0,0 +1,180 @@
+use crate::{read_buffered, CatrError};
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Read};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::vec::IntoIter;
+
+/// The most worker threads `--jobs` may ask for.
+pub const MAX_JOBS: usize = 64;
+
+/// How much a worker reads at a time.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// How many chunks of one file may wait to be written, which bounds the
+/// memory read-ahead uses to `jobs * CHUNKS * CHUNK_SIZE`.
+const CHUNKS: usize = 16;
+
+type Open = dyn Fn(&str) -> io::Result<Box<dyn BufRead>> + Send + Sync;
+
+/// What a worker sends back about a file: first whether it opened, then
+/// its contents a chunk at a time until the channel closes at the end.
+enum Message {
+    Opened,
+    Chunk(Vec<u8>),
+    Failed(io::Error),
+}
+
+/// A file to read and where to send its contents.
+struct Job {
+    filename: String,
+    chunks: SyncSender<Message>,
+}
+
+/// Reads files ahead of time on worker threads for `--jobs`, handing them
+/// back in their original order as they are read. At most `jobs` files are
+/// read at once, each only a bounded number of chunks ahead.
+pub struct Prefetch {
+    files: IntoIter<String>,
+    /// Dropped with the `Prefetch`, which lets idle workers exit.
+    queue: Sender<Job>,
+    pending: VecDeque<(String, Receiver<Message>)>,
+}
+
+impl Prefetch {
+    /// Starts up to `jobs` workers, no more than there are files, each
+    /// opening files with `open`.
+    pub fn new<F>(files: Vec<String>, jobs: usize, open: F) -> io::Result<Self>
+    where
+        F: Fn(&str) -> io::Result<Box<dyn BufRead>> + Send + Sync + 'static,
+    {
+        let jobs = jobs.min(files.len());
+        let (queue, jobs_rx) = mpsc::channel::<Job>();
+        let jobs_rx = Arc::new(Mutex::new(jobs_rx));
+        let open: Arc<Open> = Arc::new(open);
+        for _ in 0..jobs {
+            let jobs_rx = Arc::clone(&jobs_rx);
+            let open = Arc::clone(&open);
+            // Any workers already started exit once `queue` is dropped.
+            thread::Builder::new().spawn(move || loop {
+                let job = match jobs_rx.lock() {
+                    Ok(jobs_rx) => jobs_rx.recv(),
+                    Err(_) => return,
+                };
+                let Ok(job) = job else { return };
+                fetch(&job.filename, &*open, &job.chunks);
+            })?;
+        }
+        let mut prefetch = Prefetch {
+            files: files.into_iter(),
+            queue,
+            pending: VecDeque::with_capacity(jobs),
+        };
+        for _ in 0..jobs {
+            prefetch.submit();
+        }
+        Ok(prefetch)
+    }
+
+    fn submit(&mut self) {
+        let Some(filename) = self.files.next() else {
+            return;
+        };
+        let (chunks, receiver) = mpsc::sync_channel(CHUNKS);
+        let job = Job {
+            filename: filename.clone(),
+            chunks,
+        };
+        if self.queue.send(job).is_ok() {
+            self.pending.push_back((filename, receiver));
+        }
+    }
+
+    /// Waits for the next file to open, starting on another.
+    pub fn next(&mut self) -> Option<Result<Fetched, CatrError>> {
+        let (filename, receiver) = self.pending.pop_front()?;
+        self.submit();
+        let source = match receiver.recv() {
+            Ok(Message::Opened) => {
+                return Some(Ok(Fetched {
+                    receiver,
+                    chunk: Vec::new(),
+                    pos: 0,
+                }))
+            }
+            Ok(Message::Failed(err)) => err,
+            _ => io::Error::other("read-ahead stopped"),
+        };
+        Some(Err(CatrError::Open {
+            path: filename,
+            source,
+        }))
+    }
+}
+
+/// Sends `filename` to `chunks` a chunk at a time, stopping as soon as the
+/// reader hangs up, which it does once it has read all it wants.
+fn fetch(filename: &str, open: &Open, chunks: &SyncSender<Message>) {
+    let mut file = match open(filename) {
+        Ok(file) => file,
+        Err(err) => {
+            let _ = chunks.send(Message::Failed(err));
+            return;
+        }
+    };
+    if chunks.send(Message::Opened).is_err() {
+        return;
+    }
+    loop {
+        let mut chunk = vec![0; CHUNK_SIZE];
+        let message = match file.read(&mut chunk) {
+            Ok(0) => return,
+            Ok(n) => {
+                chunk.truncate(n);
+                Message::Chunk(chunk)
+            }
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => Message::Failed(err),
+        };
+        let failed = matches!(message, Message::Failed(_));
+        if chunks.send(message).is_err() || failed {
+            return;
+        }
+    }
+}
+
+/// A file being read ahead, read back in order as its chunks arrive.
+pub struct Fetched {
+    receiver: Receiver<Message>,
+    chunk: Vec<u8>,
+    pos: usize,
+}
+
+impl Read for Fetched {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        read_buffered(self, buf)
+    }
+}
+
+impl BufRead for Fetched {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        while self.pos == self.chunk.len() {
+            match self.receiver.recv() {
+                Ok(Message::Chunk(chunk)) => {
+                    self.chunk = chunk;
+                    self.pos = 0;
+                }
+                Ok(Message::Failed(err)) => return Err(err),
+                Ok(Message::Opened) => {}
+                // The worker closes the channel at the end of the file.
+                Err(_) => break,
+            }
+        }
+        Ok(&self.chunk[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.chunk.len());
+    }
+}
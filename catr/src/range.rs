@@ -0,0 +1,183 @@
+use crate::MyResult;
+
+/// A line position in a `--line-range` expression. Positions are 1-based;
+/// `FromEnd(1)` is the last line of the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineIndex {
+    FromStart(usize),
+    FromEnd(usize),
+}
+
+impl LineIndex {
+    fn parse(value: &str) -> Option<Self> {
+        match value.strip_prefix('-') {
+            Some(rest) => match rest.parse::<usize>() {
+                Ok(0) | Err(_) => None,
+                Ok(n) => Some(LineIndex::FromEnd(n)),
+            },
+            None => value.parse::<usize>().ok().map(LineIndex::FromStart),
+        }
+    }
+}
+
+/// A single `start:end` range, inclusive on both ends. A missing bound is
+/// open, so `5:` runs to the end of the input and `:20` starts at line 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineRange {
+    start: Option<LineIndex>,
+    end: Option<LineIndex>,
+}
+
+impl LineRange {
+    fn parse(value: &str) -> MyResult<Self> {
+        let invalid = |reason: &str| format!("Invalid line range \"{}\": {}", value, reason);
+        let index = |bound: &str| match bound.trim() {
+            "" => Ok(None),
+            bound => LineIndex::parse(bound)
+                .map(Some)
+                .ok_or_else(|| invalid(&format!("\"{}\" is not a line number", bound))),
+        };
+        let (start, end) = match value.split_once(':') {
+            Some((start, end)) => (index(start)?, index(end)?),
+            None if value.trim().is_empty() => return Err(invalid("empty range").into()),
+            None => {
+                let line = index(value)?;
+                (line, line)
+            }
+        };
+        if let (Some(LineIndex::FromStart(s)), Some(LineIndex::FromStart(e))) = (start, end) {
+            if s > e {
+                return Err(invalid("start is greater than end").into());
+            }
+        }
+        if let (Some(LineIndex::FromEnd(s)), Some(LineIndex::FromEnd(e))) = (start, end) {
+            if s < e {
+                return Err(invalid("start is greater than end").into());
+            }
+        }
+        Ok(LineRange { start, end })
+    }
+
+    /// See `LineRanges::contains` for the meaning of a missing `total`.
+    fn contains(&self, line: usize, total: Option<usize>) -> bool {
+        let after_start = match self.start {
+            None => true,
+            Some(LineIndex::FromStart(n)) => line >= n,
+            Some(LineIndex::FromEnd(n)) => {
+                total.is_some_and(|total| line.saturating_add(n) > total)
+            }
+        };
+        let before_end = match self.end {
+            None => true,
+            Some(LineIndex::FromStart(n)) => line <= n,
+            Some(LineIndex::FromEnd(n)) => {
+                total.is_none_or(|total| line.saturating_add(n) <= total.saturating_add(1))
+            }
+        };
+        after_start && before_end
+    }
+
+    /// How many lines from the end of the input this range has to see
+    /// before it can decide on a line.
+    fn window(&self) -> usize {
+        let start = match self.start {
+            Some(LineIndex::FromEnd(n)) => n,
+            _ => 0,
+        };
+        let end = match self.end {
+            Some(LineIndex::FromEnd(n)) => n.saturating_sub(1),
+            _ => 0,
+        };
+        start.max(end)
+    }
+}
+
+/// The parsed value of `--line-range`: one or more comma-separated ranges.
+/// A line is selected when any of the ranges contains it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineRanges(Vec<LineRange>);
+
+impl LineRanges {
+    pub fn parse(value: &str) -> MyResult<Self> {
+        value
+            .split(',')
+            .map(LineRange::parse)
+            .collect::<MyResult<Vec<_>>>()
+            .map(LineRanges)
+    }
+
+    /// Selects the first `lines` lines, as `--head` does.
+    pub fn head(lines: usize) -> Self {
+        LineRanges(vec![LineRange {
+            start: None,
+            end: Some(LineIndex::FromStart(lines)),
+        }])
+    }
+
+    /// Selects the last `lines` lines, as `--tail` does.
+    pub fn tail(lines: usize) -> Self {
+        LineRanges(vec![LineRange {
+            start: Some(LineIndex::FromEnd(lines)),
+            end: None,
+        }])
+    }
+
+    /// Adds the lines selected by `other` to this selection.
+    pub fn extend(&mut self, other: LineRanges) {
+        self.0.extend(other.0);
+    }
+
+    /// Whether the ranges count from the end of the input, in which case the
+    /// total number of lines has to be known before any line is selected.
+    pub fn needs_total(&self) -> bool {
+        self.window() > 0
+    }
+
+    /// The number of lines at the end of the input whose selection depends
+    /// on the total. Only these have to be held back until the input ends;
+    /// any earlier line can be decided with `contains(line, None)`.
+    pub fn window(&self) -> usize {
+        self.0.iter().map(LineRange::window).max().unwrap_or(0)
+    }
+
+    /// The last line any range can select, if they all end at a fixed line,
+    /// so reading can stop there. Ranges counted from the end need the whole
+    /// input to find the total, so never stop early for them.
+    pub fn last_line(&self) -> Option<usize> {
+        if self.needs_total() {
+            return None;
+        }
+        self.0
+            .iter()
+            .map(|range| match range.end {
+                Some(LineIndex::FromStart(n)) => Some(n),
+                _ => None,
+            })
+            .try_fold(0, |last, end| end.map(|end| last.max(end)))
+    }
+
+    /// If the ranges only select a number of lines at the end of the input,
+    /// as `--tail` does, returns that number.
+    pub fn tail_lines(&self) -> Option<usize> {
+        self.0
+            .iter()
+            .map(|range| match (range.start, range.end) {
+                (Some(LineIndex::FromEnd(n)), None) => Some(n),
+                _ => None,
+            })
+            .try_fold(0, |most, n| n.map(|n| most.max(n)))
+    }
+
+    /// Whether `line` (1-based) is selected in an input of `total` lines.
+    /// `total` may be `None` while the input is still being read, as long
+    /// as `line` is more than `window()` lines before the current one.
+    pub fn contains(&self, line: usize, total: Option<usize>) -> bool {
+        self.0.iter().any(|range| range.contains(line, total))
+    }
+
+    /// Like `contains`, for a 0-based byte offset into an input that is
+    /// `len` bytes long, as used by `--hex`.
+    pub fn contains_offset(&self, offset: usize, len: Option<usize>) -> bool {
+        self.contains(offset, len.map(|len| len.saturating_sub(1)))
+    }
+}
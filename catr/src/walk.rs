@@ -0,0 +1,66 @@
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::WalkBuilder;
+use std::path::Path;
+
+use crate::MyResult;
+
+/// Expands directories named in `files` into the files below them for
+/// `--recursive`, in sorted order. Files in `.gitignore` and similar ignore
+/// files are skipped, as are hidden files and directories. `include` and
+/// `exclude` globs are matched against paths relative to the directory;
+/// files named directly on the command line are always kept. Errors while
+/// walking are reported and counted in `failed`.
+pub fn expand(
+    files: &[String],
+    include: &[String],
+    exclude: &[String],
+    failed: &mut usize,
+) -> MyResult<Vec<String>> {
+    let include = glob_set(include)?;
+    let exclude = glob_set(exclude)?;
+    let mut expanded = Vec::with_capacity(files.len());
+    for filename in files {
+        if filename == "-" || !Path::new(filename).is_dir() {
+            expanded.push(filename.clone());
+            continue;
+        }
+        let root = Path::new(filename).to_path_buf();
+        let prune = exclude.clone();
+        let walk = WalkBuilder::new(filename)
+            .require_git(false)
+            .sort_by_file_name(|a, b| a.cmp(b))
+            .filter_entry(move |entry| {
+                let path = entry.path().strip_prefix(&root).unwrap_or(entry.path());
+                path.as_os_str().is_empty() || !prune.is_match(path)
+            })
+            .build();
+        for entry in walk {
+            match entry {
+                Ok(entry) if entry.file_type().is_some_and(|t| !t.is_dir()) => {
+                    let path = entry.path();
+                    let relative = path.strip_prefix(filename).unwrap_or(path);
+                    if include.is_empty() || include.is_match(relative) {
+                        expanded.push(path.to_string_lossy().into_owned());
+                    }
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    eprintln!("{}", err);
+                    *failed += 1;
+                }
+            }
+        }
+    }
+    Ok(expanded)
+}
+
+fn glob_set(globs: &[String]) -> MyResult<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder
+            .add(Glob::new(glob).map_err(|e| format!("Invalid glob \"{}\": {}", glob, e.kind()))?);
+    }
+    builder
+        .build()
+        .map_err(|e| format!("Invalid glob: {}", e.kind()).into())
+}
@@ -3,6 +3,11 @@ use predicates::prelude::*;
 use rand::{distributions::Alphanumeric, Rng};
 use std::error::Error;
 use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::Stdio;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
 
 type TestResult = Result<(), Box<dyn Error>>;
 
@@ -11,6 +16,13 @@ const EMPTY: &str = "tests/inputs/empty.txt";
 const FOX: &str = "tests/inputs/fox.txt";
 const SPIDERS: &str = "tests/inputs/spiders.txt";
 const BUSTLE: &str = "tests/inputs/the-bustle.txt";
+const CONTROL: &str = "tests/inputs/control.txt";
+const BINARY: &str = "tests/inputs/binary.dat";
+const BLANKS: &str = "tests/inputs/blanks.txt";
+const NUMBERS: &str = "tests/inputs/numbers.txt";
+const BUSTLE_GZ: &str = "tests/inputs/the-bustle.txt.gz";
+const DIR: &str = "tests/inputs/dir";
+const MIXED: &str = "tests/inputs/mixed.txt";
 
 // --------------------------------------------------
 #[test]
@@ -47,7 +59,7 @@ fn skips_bad_file() -> TestResult {
     Command::cargo_bin(PRG)?
         .arg(&bad)
         .assert()
-        .success()
+        .failure()
         .stderr(predicate::str::is_match(expected)?);
     Ok(())
 }
@@ -63,6 +75,17 @@ fn run(args: &[&str], expected_file: &str) -> TestResult {
     Ok(())
 }
 
+// --------------------------------------------------
+fn run_bytes(args: &[&str], expected_file: &str) -> TestResult {
+    let expected = fs::read(expected_file)?;
+    Command::cargo_bin(PRG)?
+        .args(args)
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
 // --------------------------------------------------
 fn run_stdin(input_file: &str, args: &[&str], expected_file: &str) -> TestResult {
     let input = fs::read_to_string(input_file)?;
@@ -212,10 +235,1284 @@ fn range_b() -> TestResult {
     )
 }
 
-// #[test]
-// fn non_printing() -> TestResult {
-//     run(
-//         &[SPIDERS, FOX, BUSTLE, "-e"],
-//         "tests/expected/non_printing_a.out",
-//     )
-// }
+// --------------------------------------------------
+#[test]
+fn range_open_end() -> TestResult {
+    run(&[BUSTLE, "-r", "5:"], "tests/expected/range_open_end.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn range_open_start() -> TestResult {
+    run(&[BUSTLE, "-r", ":3"], "tests/expected/range_open_start.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn range_from_end() -> TestResult {
+    run(&[BUSTLE, "-r", "-3:"], "tests/expected/range_from_end.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn range_from_end_to_line() -> TestResult {
+    run(
+        &[BUSTLE, "-r", "-8:3"],
+        "tests/expected/range_from_end_to_line.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn range_multiple() -> TestResult {
+    run(
+        &[BUSTLE, "--line-range", "1:2,8:9"],
+        "tests/expected/range_multiple.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn range_from_end_stdin() -> TestResult {
+    run_stdin(BUSTLE, &["-r", "-3:"], "tests/expected/range_from_end.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn range_invalid() -> TestResult {
+    for range in &["a:b", "10:2", "1:x", "-0:", ""] {
+        Command::cargo_bin(PRG)?
+            .args([BUSTLE, "-r", range])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("Invalid line range"));
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn non_printing() -> TestResult {
+    run(
+        &[SPIDERS, FOX, BUSTLE, "-e"],
+        "tests/expected/non_printing_a.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn control_e() -> TestResult {
+    run(&["-e", CONTROL], "tests/expected/control.txt.e.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn binary() -> TestResult {
+    run_bytes(&[BINARY], "tests/expected/binary.dat.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn binary_n() -> TestResult {
+    run_bytes(&["-n", BINARY], "tests/expected/binary.dat.n.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn binary_b() -> TestResult {
+    run_bytes(&["-b", BINARY], "tests/expected/binary.dat.b.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn binary_e() -> TestResult {
+    run_bytes(&["-e", BINARY], "tests/expected/binary.dat.e.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn control_v() -> TestResult {
+    run(&["-v", CONTROL], "tests/expected/control.txt.v.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn control_t() -> TestResult {
+    run(&["-t", CONTROL], "tests/expected/control.txt.t.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn control_show_all() -> TestResult {
+    run(&["--show-all", CONTROL], "tests/expected/control.txt.A.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn control_n_show_all() -> TestResult {
+    run(&["-n", "-A", CONTROL], "tests/expected/control.txt.nA.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn blanks_s() -> TestResult {
+    run(&["-s", BLANKS], "tests/expected/blanks.txt.s.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn blanks_sn() -> TestResult {
+    run(&["-s", "-n", BLANKS], "tests/expected/blanks.txt.sn.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn blanks_sb() -> TestResult {
+    run(
+        &["--squeeze-blank", "-b", BLANKS],
+        "tests/expected/blanks.txt.sb.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn blanks_show_ends() -> TestResult {
+    run(&["--show-ends", BLANKS], "tests/expected/blanks.txt.E.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn blanks_show_tabs() -> TestResult {
+    run(&["--show-tabs", BLANKS], "tests/expected/blanks.txt.T.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn blanks_show_all() -> TestResult {
+    run(&["-A", BLANKS], "tests/expected/blanks.txt.A.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn all_continuous_n() -> TestResult {
+    run(
+        &[FOX, SPIDERS, BUSTLE, "-n", "--continuous"],
+        "tests/expected/all.continuous.n.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn all_continuous_b() -> TestResult {
+    run(
+        &[FOX, SPIDERS, BUSTLE, "-b", "--continuous"],
+        "tests/expected/all.continuous.b.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn all_continuous_range() -> TestResult {
+    run(
+        &[FOX, SPIDERS, BUSTLE, "-r", "3:6", "--continuous"],
+        "tests/expected/all.continuous.range.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn all_continuous_range_from_end() -> TestResult {
+    run(
+        &[FOX, SPIDERS, BUSTLE, "-r", "-2:", "--continuous"],
+        "tests/expected/all.continuous.range_from_end.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn continuous_skips_bad_file() -> TestResult {
+    let bad = gen_bad_file();
+    let expected = fs::read_to_string("tests/expected/all.continuous.n.out")?;
+    Command::cargo_bin(PRG)?
+        .args([FOX, SPIDERS, &bad, BUSTLE, "-n", "--continuous"])
+        .assert()
+        .failure()
+        .stdout(expected)
+        .stderr(predicate::str::contains(bad));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn paging_always_uses_pager_env() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--paging=always", FOX])
+        .env("PAGER", "sed s/^/>/")
+        .assert()
+        .success()
+        .stdout(">The quick brown fox jumps over the lazy dog.\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn paging_never_ignores_pager_env() -> TestResult {
+    let expected = fs::read_to_string("tests/expected/fox.txt.out")?;
+    Command::cargo_bin(PRG)?
+        .args(["--paging", "never", FOX])
+        .env("PAGER", "sed s/^/>/")
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn paging_stops_when_pager_exits() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--paging=always", BUSTLE, BUSTLE, BUSTLE])
+        .env("PAGER", "head -n 1")
+        .assert()
+        .success()
+        .stdout("The bustle in a house\n")
+        .stderr("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn paging_falls_back_to_internal_pager() -> TestResult {
+    let expected = fs::read_to_string("tests/expected/fox.txt.out")?;
+    Command::cargo_bin(PRG)?
+        .args(["--paging=always", FOX])
+        .env_remove("PAGER")
+        .env("PATH", "")
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn paging_invalid() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--paging=sometimes", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("sometimes"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn decompress_formats() -> TestResult {
+    for ext in &["gz", "bz2", "xz", "zst"] {
+        let input = format!("{}.{}", BUSTLE, ext);
+        run(&["-z", "-n", &input], "tests/expected/the-bustle.txt.n.out")?;
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn decompress_range() -> TestResult {
+    run(
+        &["--decompress", BUSTLE_GZ, "-r", "-3:"],
+        "tests/expected/range_from_end.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn decompress_stdin() -> TestResult {
+    let input = fs::read(BUSTLE_GZ)?;
+    let expected = fs::read_to_string("tests/expected/the-bustle.txt.b.out")?;
+    Command::cargo_bin(PRG)?
+        .args(["-z", "-b"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn decompress_multiple_members() -> TestResult {
+    run(
+        &["-z", "tests/inputs/multi.gz"],
+        "tests/expected/multi.gz.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn decompress_passes_through_plain_input() -> TestResult {
+    run(&["-z", BUSTLE], "tests/expected/the-bustle.txt.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn compressed_without_decompress() -> TestResult {
+    run_bytes(&[BUSTLE_GZ], BUSTLE_GZ)
+}
+
+// --------------------------------------------------
+#[test]
+fn highlight_by_extension() -> TestResult {
+    for ext in &["rs", "toml", "json"] {
+        let input = format!("tests/inputs/sample.{}", ext);
+        let expected = format!("tests/expected/sample.{}.highlight.out", ext);
+        run(&["--highlight", "--color=always", &input], &expected)?;
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn highlight_forced_language() -> TestResult {
+    let input = fs::read_to_string("tests/inputs/sample.json")?;
+    let expected = fs::read_to_string("tests/expected/sample.json.highlight.out")?;
+    Command::cargo_bin(PRG)?
+        .args(["--highlight=json", "--color=always"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn highlight_numbered() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--highlight", "--color=always", "-n", "-r", "2:3"])
+        .arg("tests/inputs/sample.rs")
+        .assert()
+        .success()
+        .stdout(
+            "     2\t\x1b[90m   spanning /* nested */ lines */\x1b[0m\n     \
+             3\t\x1b[34m#[derive(Debug)]\x1b[0m\n",
+        );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn highlight_tail_inside_comment() -> TestResult {
+    run(
+        &[
+            "--highlight",
+            "--color=always",
+            "--tail",
+            "13",
+            "tests/inputs/sample.rs",
+        ],
+        "tests/expected/sample.rs.highlight.tail13.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn highlight_without_color() -> TestResult {
+    for args in &[vec!["--highlight"], vec!["--highlight", "--color=never"]] {
+        Command::cargo_bin(PRG)?
+            .args(args)
+            .arg("tests/inputs/sample.rs")
+            .assert()
+            .success()
+            .stdout(fs::read_to_string("tests/inputs/sample.rs")?);
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn hex() -> TestResult {
+    run(&["-x", BINARY], "tests/expected/binary.dat.x.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn hex_width_group() -> TestResult {
+    run(
+        &["--hex", "--hex-width", "8", "--hex-group", "4", BINARY],
+        "tests/expected/binary.dat.x8g4.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn hex_byte_range() -> TestResult {
+    run(
+        &["-x", "-r", "4:20,-8:", BINARY],
+        "tests/expected/binary.dat.x.range.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn hex_invalid_width() -> TestResult {
+    for width in &["0", "wide", "257", "100000000000"] {
+        Command::cargo_bin(PRG)?
+            .args(["-x", "--hex-width", width, BINARY])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("Invalid hex width"));
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_appends_truncation_and_rotation() -> TestResult {
+    let path = std::env::temp_dir().join(format!("catr-follow-{}", gen_bad_file()));
+    let rotated = path.with_extension("1");
+    fs::write(&path, "one\n")?;
+
+    let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin(PRG))
+        .args(["-n", "-f"])
+        .arg(&path)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let stdout = child.stdout.take().unwrap();
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            if tx.send(line.unwrap()).is_err() {
+                break;
+            }
+        }
+    });
+    let next = || rx.recv_timeout(Duration::from_secs(5));
+
+    let result = (|| -> TestResult {
+        assert_eq!(next()?, "     1\tone");
+        let append = |text: &str| -> std::io::Result<()> {
+            fs::OpenOptions::new()
+                .append(true)
+                .open(&path)?
+                .write_all(text.as_bytes())
+        };
+        append("two\n")?;
+        assert_eq!(next()?, "     2\ttwo");
+        fs::write(&path, "3\n")?;
+        assert_eq!(next()?, "     3\t3");
+        fs::rename(&path, &rotated)?;
+        fs::write(&path, "four\n")?;
+        assert_eq!(next()?, "     4\tfour");
+        Ok(())
+    })();
+
+    child.kill()?;
+    child.wait()?;
+    fs::remove_file(&path).ok();
+    fs::remove_file(&rotated).ok();
+    result
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_shows_partial_line() -> TestResult {
+    let path = std::env::temp_dir().join(format!("catr-follow-{}", gen_bad_file()));
+    fs::write(&path, "one\ntwo")?;
+
+    let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin(PRG))
+        .args(["-n", "-f"])
+        .arg(&path)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let mut stdout = child.stdout.take().unwrap();
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buf = [0; 1024];
+        while let Ok(len @ 1..) = stdout.read(&mut buf) {
+            if tx.send(buf[..len].to_vec()).is_err() {
+                break;
+            }
+        }
+    });
+    let mut output = Vec::new();
+    let mut wait_for = |expected: &str| -> TestResult {
+        while !output.ends_with(expected.as_bytes()) {
+            output.extend(rx.recv_timeout(Duration::from_secs(5))?);
+        }
+        Ok(())
+    };
+
+    let result = (|| -> TestResult {
+        wait_for("     1\tone\n     2\ttwo")?;
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&path)?
+            .write_all(b" more\nthree\n")?;
+        wait_for("     2\ttwo more\n     3\tthree\n")?;
+        Ok(())
+    })();
+
+    child.kill()?;
+    child.wait()?;
+    fs::remove_file(&path).ok();
+    result
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_rejects_decompress() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-f", "-z", BUSTLE_GZ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_rejects_ranges_from_end() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-f", "-r", "-2:", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--follow"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn head() -> TestResult {
+    run(
+        &["--head", "4", BUSTLE],
+        "tests/expected/the-bustle.txt.head4.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tail() -> TestResult {
+    run(
+        &["--tail", "4", BUSTLE],
+        "tests/expected/the-bustle.txt.tail4.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tail_stdin() -> TestResult {
+    run_stdin(
+        BUSTLE,
+        &["--tail", "4"],
+        "tests/expected/the-bustle.txt.tail4.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tail_n() -> TestResult {
+    run(
+        &["-n", "--tail", "4", BUSTLE],
+        "tests/expected/the-bustle.txt.n.tail4.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn head_and_tail() -> TestResult {
+    run(
+        &["--head", "2", "--tail", "2", BUSTLE],
+        "tests/expected/the-bustle.txt.head2.tail2.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tail_longer_than_input() -> TestResult {
+    run(
+        &["--tail", "100", BUSTLE],
+        "tests/expected/the-bustle.txt.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tail_huge() -> TestResult {
+    run(
+        &["--tail", "1000000000", BUSTLE],
+        "tests/expected/the-bustle.txt.out",
+    )?;
+    run(
+        &[BUSTLE, "-r", "-18446744073709551615:"],
+        "tests/expected/the-bustle.txt.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tail_invalid() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--tail", "three", BUSTLE])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid --tail line count"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn head_tail_reject_hex() -> TestResult {
+    for flag in ["--head", "--tail"] {
+        Command::cargo_bin(PRG)?
+            .args(["-x", flag, "4", FOX])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("cannot be used with"));
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn match_numbered_context() -> TestResult {
+    run(
+        &["-n", "--match", r"\bup\b", "-C", "1", BUSTLE],
+        "tests/expected/the-bustle.txt.match.n.C1.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn match_invert() -> TestResult {
+    run(
+        &["--grep", "e", "--invert-match", BUSTLE],
+        "tests/expected/the-bustle.txt.invert.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn match_before_after_context() -> TestResult {
+    run(
+        &[
+            "--match",
+            "5$",
+            "--before-context",
+            "2",
+            "--after-context",
+            "1",
+            NUMBERS,
+        ],
+        "tests/expected/numbers.txt.match.B2.A1.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn match_invalid_pattern() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--match", "(", BUSTLE])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid --match pattern"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn highlight_pattern_numbered_range() -> TestResult {
+    run(
+        &[
+            "--highlight-pattern",
+            "the|a",
+            "--color=always",
+            "-n",
+            "-r",
+            "5:7",
+            BUSTLE,
+        ],
+        "tests/expected/the-bustle.txt.highlight_pattern.n.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn highlight_pattern_nonblank() -> TestResult {
+    run(
+        &[
+            "--highlight-pattern",
+            r"[A-Z]\w+",
+            "--color=always",
+            "-b",
+            BUSTLE,
+        ],
+        "tests/expected/the-bustle.txt.highlight_pattern.b.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn highlight_pattern_without_color() -> TestResult {
+    run(
+        &["--highlight-pattern", "the", "--color=never", BUSTLE],
+        "tests/expected/the-bustle.txt.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn directory_is_an_error() -> TestResult {
+    let expected = fs::read_to_string("tests/expected/fox.txt.out")?;
+    Command::cargo_bin(PRG)?
+        .args([DIR, FOX])
+        .assert()
+        .failure()
+        .stdout(expected)
+        .stderr(format!("Failed to open {}: Is a directory\n", DIR));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn recursive() -> TestResult {
+    run(&["-R", DIR], "tests/expected/dir.R.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn recursive_include() -> TestResult {
+    run(
+        &["-R", "--include", "*.txt", DIR],
+        "tests/expected/dir.R.include.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn recursive_exclude() -> TestResult {
+    run(
+        &["-R", "--exclude", "sub", DIR],
+        "tests/expected/dir.R.exclude.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn headers() -> TestResult {
+    run(
+        &["--headers", FOX, SPIDERS, BUSTLE],
+        "tests/expected/all.headers.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn headers_skip_bad_file() -> TestResult {
+    let bad = gen_bad_file();
+    let expected = fs::read_to_string("tests/expected/all.headers.out")?;
+    Command::cargo_bin(PRG)?
+        .args(["--headers", FOX, &bad, SPIDERS, BUSTLE])
+        .assert()
+        .failure()
+        .stdout(expected)
+        .stderr(predicate::str::contains(bad));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn headers_json() -> TestResult {
+    run(
+        &["--headers=json", FOX, SPIDERS],
+        "tests/expected/headers.json.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn headers_color() -> TestResult {
+    run(
+        &["--headers", "--color=always", FOX, SPIDERS],
+        "tests/expected/headers.color.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn fail_fast() -> TestResult {
+    let bad = gen_bad_file();
+    let expected = fs::read_to_string("tests/expected/fox.txt.out")?;
+    Command::cargo_bin(PRG)?
+        .args([FOX, &bad, SPIDERS, "--fail-fast"])
+        .assert()
+        .failure()
+        .stdout(expected)
+        .stderr(predicate::str::contains(bad));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn continuous_fail_fast() -> TestResult {
+    let bad = gen_bad_file();
+    let expected = fs::read_to_string("tests/expected/fox.txt.n.out")?;
+    Command::cargo_bin(PRG)?
+        .args([FOX, &bad, SPIDERS, "-n", "--continuous", "--fail-fast"])
+        .assert()
+        .failure()
+        .stdout(expected)
+        .stderr(predicate::str::contains(bad));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn keep_going_overrides_fail_fast() -> TestResult {
+    let bad = gen_bad_file();
+    let expected = fs::read_to_string("tests/expected/all.out")?;
+    Command::cargo_bin(PRG)?
+        .args([FOX, SPIDERS, &bad, BUSTLE, "--fail-fast", "--keep-going"])
+        .assert()
+        .failure()
+        .stdout(expected)
+        .stderr(predicate::str::contains(bad));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn output_jsonl() -> TestResult {
+    run(
+        &["--output=jsonl", FOX, SPIDERS],
+        "tests/expected/jsonl.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn output_jsonl_number_nonblank_squeeze() -> TestResult {
+    run(
+        &["--output=jsonl", "-b", "-s", BLANKS],
+        "tests/expected/blanks.txt.jsonl.b.s.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn output_jsonl_match_context() -> TestResult {
+    run(
+        &["--output=jsonl", "--match", "sweep", "-C", "1", BUSTLE],
+        "tests/expected/the-bustle.txt.jsonl.match.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn number_format() -> TestResult {
+    run(
+        &[
+            "-n",
+            "--number-format=rz",
+            "--number-width",
+            "3",
+            "--number-separator",
+            ": ",
+            "--starting-line-number",
+            "10",
+            "--line-increment",
+            "5",
+            BUSTLE,
+        ],
+        "tests/expected/the-bustle.txt.n.number_format.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn number_format_nonblank() -> TestResult {
+    run(
+        &[
+            "-b",
+            "--number-format=ln",
+            "--number-width",
+            "2",
+            "--number-separator",
+            " | ",
+            BUSTLE,
+        ],
+        "tests/expected/the-bustle.txt.b.number_format.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn number_width_too_wide() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-n", "--number-width", "70000", FOX])
+        .assert()
+        .failure()
+        .stderr("Invalid line number width \"70000\": must be at most 1024\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn number_does_not_overflow() -> TestResult {
+    let max = usize::MAX.to_string();
+    Command::cargo_bin(PRG)?
+        .args([
+            "-n",
+            "--starting-line-number",
+            &max,
+            "--line-increment",
+            &max,
+        ])
+        .arg(SPIDERS)
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(format!("{}\t", max)));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn number_format_requires_numbering() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--number-width", "3", BUSTLE])
+        .assert()
+        .failure()
+        .stderr("--number-width requires --number or --number-nonblank\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn plain_copy_with_stdin() -> TestResult {
+    let input = fs::read_to_string(BUSTLE)?;
+    let expected = fs::read("tests/expected/fox.stdin.binary.out")?;
+    Command::cargo_bin(PRG)?
+        .args([FOX, "-", BINARY])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn plain_copy_to_file() -> TestResult {
+    let path = std::env::temp_dir().join(format!("catr-copy-{}", gen_bad_file()));
+    let status = std::process::Command::new(assert_cmd::cargo::cargo_bin(PRG))
+        .args(["--headers", FOX, BINARY])
+        .stdout(fs::File::create(&path)?)
+        .status()?;
+    let output = fs::read(&path);
+    fs::remove_file(&path)?;
+
+    let mut expected = b"==> tests/inputs/fox.txt <==\n".to_vec();
+    expected.extend(fs::read(FOX)?);
+    expected.extend(b"\n==> tests/inputs/binary.dat <==\n");
+    expected.extend(fs::read(BINARY)?);
+    assert!(status.success());
+    assert_eq!(output?, expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn jobs_preserve_order() -> TestResult {
+    run(&["-j", "2", FOX, SPIDERS, BUSTLE], "tests/expected/all.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn jobs_with_formatting() -> TestResult {
+    run(
+        &["--jobs", "8", "--headers", FOX, SPIDERS, BUSTLE],
+        "tests/expected/all.headers.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(target_os = "linux")]
+fn write_error_ends_run() -> TestResult {
+    for args in [vec![FOX, SPIDERS], vec!["-n", FOX, SPIDERS]] {
+        let output = std::process::Command::new(assert_cmd::cargo::cargo_bin(PRG))
+            .args(args)
+            .stdout(fs::File::create("/dev/full")?)
+            .output()?;
+        assert!(!output.status.success());
+        assert_eq!(
+            String::from_utf8(output.stderr)?,
+            "No space left on device (os error 28)\n"
+        );
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn jobs_skip_bad_file() -> TestResult {
+    let bad = gen_bad_file();
+    let expected = fs::read_to_string("tests/expected/all.out")?;
+    Command::cargo_bin(PRG)?
+        .args(["-j", "3", FOX, &bad, SPIDERS, BUSTLE])
+        .assert()
+        .failure()
+        .stdout(expected)
+        .stderr(format!(
+            "Failed to open {}: No such file or directory (os error 2)\n",
+            bad
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn jobs_head_and_tail() -> TestResult {
+    run(
+        &["-j", "2", "--head", "2", "--tail", "2", BUSTLE],
+        "tests/expected/the-bustle.txt.head2.tail2.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn jobs_too_many() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-j", "200000", FOX, SPIDERS])
+        .assert()
+        .failure()
+        .stderr("Invalid job count \"200000\": must be at most 64\n");
+    run(
+        &["-j", "64", FOX, SPIDERS, BUSTLE],
+        "tests/expected/all.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn jobs_with_stdin() -> TestResult {
+    let input = fs::read_to_string(BUSTLE)?;
+    let expected = fs::read_to_string("tests/expected/all.out")?;
+    Command::cargo_bin(PRG)?
+        .args(["-j", "2", FOX, SPIDERS, "-"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn utf16_with_bom() -> TestResult {
+    run(
+        &["-n", "--strip-bom", "tests/inputs/spiders.utf16le.txt"],
+        "tests/expected/spiders.txt.n.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn utf16_line_range() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-r", "2", "--strip-bom", "tests/inputs/spiders.utf16le.txt"])
+        .assert()
+        .success()
+        .stdout("I keep house\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn from_encoding_latin1() -> TestResult {
+    run(
+        &["-n", "--from-encoding", "latin1", "tests/inputs/latin1.txt"],
+        "tests/expected/latin1.txt.n.from_encoding.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn to_encoding_latin1() -> TestResult {
+    run_bytes(
+        &[
+            "--from-encoding",
+            "latin1",
+            "--to-encoding",
+            "latin1",
+            "tests/inputs/latin1.txt",
+        ],
+        "tests/expected/latin1.txt.roundtrip.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn to_encoding_utf16() -> TestResult {
+    run_bytes(
+        &["--to-encoding", "utf-16be", "tests/inputs/bom.txt"],
+        "tests/expected/bom.txt.utf16be.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn bom_kept_by_default() -> TestResult {
+    run_bytes(&["tests/inputs/bom.txt"], "tests/inputs/bom.txt")
+}
+
+// --------------------------------------------------
+#[test]
+fn invalid_encoding() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--from-encoding", "klingon", FOX])
+        .assert()
+        .failure()
+        .stderr("Invalid encoding \"klingon\"\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn crlf_keep() -> TestResult {
+    run_bytes(&["--crlf=keep", MIXED], MIXED)
+}
+
+// --------------------------------------------------
+#[test]
+fn crlf_strip() -> TestResult {
+    run_bytes(
+        &["--crlf=strip", MIXED],
+        "tests/expected/mixed.txt.crlf_strip.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn crlf_strip_squeeze_blank() -> TestResult {
+    run_bytes(
+        &["--crlf=strip", "-n", "-s", MIXED],
+        "tests/expected/mixed.txt.crlf_strip.n.s.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn crlf_add() -> TestResult {
+    run_bytes(
+        &["--crlf=add", MIXED],
+        "tests/expected/mixed.txt.crlf_add.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn show_cr() -> TestResult {
+    run_bytes(
+        &["--show-cr", MIXED],
+        "tests/expected/mixed.txt.show_cr.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn crlf_add_show_cr_ends() -> TestResult {
+    run_bytes(
+        &["--crlf=add", "--show-cr", "-E", MIXED],
+        "tests/expected/mixed.txt.crlf_add.show_cr.E.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn wrap_numbered() -> TestResult {
+    run(
+        &["-n", "--wrap=20", BUSTLE],
+        "tests/expected/the-bustle.txt.n.wrap.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn wrap() -> TestResult {
+    run(
+        &["--wrap=12", SPIDERS],
+        "tests/expected/spiders.txt.wrap.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn truncate() -> TestResult {
+    run(
+        &["-n", "--truncate=20", BUSTLE],
+        "tests/expected/the-bustle.txt.n.truncate.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn expand_tabs() -> TestResult {
+    run(
+        &["-n", "--expand-tabs=4", BLANKS],
+        "tests/expected/blanks.txt.n.expand_tabs.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn truncate_counts_end_markers() -> TestResult {
+    run(
+        &["-E", "--show-cr", "--truncate=6", MIXED],
+        "tests/expected/mixed.txt.E.show_cr.truncate6.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn wrap_and_truncate_conflict() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--wrap=10", "--truncate=10", FOX])
+        .assert()
+        .failure();
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn zero_wrap_width() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--wrap=0", FOX])
+        .assert()
+        .failure()
+        .stderr("Invalid wrap width \"0\"\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn wrap_narrower_than_line_numbers() -> TestResult {
+    for arg in ["--wrap=3", "--truncate=7"] {
+        Command::cargo_bin(PRG)?
+            .args(["-n", arg, FOX])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("line numbers take 8 columns"));
+    }
+    run(
+        &[
+            "-n",
+            "--number-width=2",
+            "--number-separator= ",
+            "--truncate=6",
+            FOX,
+        ],
+        "tests/expected/fox.txt.n.truncate6.out",
+    )
+}
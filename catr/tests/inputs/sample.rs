@@ -0,0 +1,14 @@
+/* A block comment
+   spanning /* nested */ lines */
+#[derive(Debug)]
+pub struct Point<'a> {
+    name: &'a str, // trailing comment
+}
+
+fn main() {
+    let raw = r#"raw "string""#;
+    let c = '\n';
+    println!("{} {}", raw, 0x1F + 1_000u32 + 2.5e3 as u32);
+    let s = "multi
+line";
+}